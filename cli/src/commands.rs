@@ -1,144 +1,65 @@
-use std::io::{self, Read, Write};
-
-use serde_json;
+use std::collections::HashMap;
+use std::io::{self, Write};
 
 use syl_lib::{
-    commands::{
-        Add, Delete, Edit, Error as CommandError, Interface, RenameTag, Result, Search, Tags,
-    },
-    config::Server,
-    db::Bookmark,
+    colors::{self, Color},
+    commands::Interface,
+    suggest::TagSuggestion,
+    tags::TagInfo,
     util::singular_plural,
 };
 
-pub fn confirm_delete(bookmarks: &Vec<Bookmark>) -> bool {
-    for (i, bookmark) in bookmarks.iter().enumerate() {
-        if i > 0 {
-            print!("\n");
-        }
-        println!("{bookmark}");
-    }
-    let mut confirm = String::from("");
-    while confirm != "y" && confirm != "n" {
-        confirm = String::from("");
-        print!(
-            "Are you sure you want to delete {} {} (y/N)? ",
-            singular_plural("these", bookmarks.len() as isize),
-            singular_plural("bookmarks", bookmarks.len() as isize)
-        );
-        io::stdout().flush().ok();
-        let stdin = io::stdin();
-        stdin.take(1).read_to_string(&mut confirm).ok();
-        if confirm == "\n" {
-            confirm = String::from("n")
-        } else if confirm == "y" {
-            eprintln!(
-                "confirm is: {:?}, doesn't match? {}",
-                confirm,
-                confirm != "y"
-            );
-            break;
-        }
-    }
-    confirm == "y"
-}
-
-pub struct ServerInterface {
-    url: String,
-    username: String,
-    password: String,
+/// Loads every tag's details and colors tags as set with `syl tags edit --color` from then on.
+/// The details only decorate the output, so they're left out if they can't be loaded; call this
+/// after the command's own request has worked, so an unreachable server isn't waited on twice.
+pub fn load_tag_details(interface: &dyn Interface) -> HashMap<String, TagInfo> {
+    let details: HashMap<String, TagInfo> = interface
+        .tag_infos()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|info| (info.name.clone(), info))
+        .collect();
+    colors::set_tag_colors(
+        details
+            .values()
+            .filter_map(|info| Some((info.name.clone(), Color::from_name(info.color.as_deref()?)?)))
+            .collect(),
+    );
+    details
 }
 
-impl ServerInterface {
-    pub fn new(server: Server) -> Self {
-        Self {
-            url: server.url,
-            username: server.username,
-            password: server.password,
-        }
-    }
-
-    fn request(&self, verb: &str, path: &str, body: Option<&str>) -> Result<String> {
-        let mut request = ureq::request(verb, &(self.url.to_string() + path))
-            .set("X-Username", &self.username)
-            .set("X-Password", &self.password);
-        let result;
-        if let Some(body) = body {
-            request = request.set("Content-Type", "application/json");
-            result = request.send_string(&body);
-        } else {
-            result = request.call();
-        }
-
-        let result = result.map_err(CommandError::UreqError)?;
-
-        result.into_string().map_err(CommandError::IOError)
-    }
+/// Asks whether to go ahead with deleting `count` bookmarks, defaulting to no
+pub fn confirm_delete(count: usize) -> bool {
+    print!(
+        "Are you sure you want to delete {} {} (y/N)? ",
+        singular_plural("these", count as isize),
+        singular_plural("bookmarks", count as isize)
+    );
+    io::stdout().flush().ok();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok();
+    matches!(answer.trim(), "y" | "Y" | "yes")
 }
 
-impl Interface for ServerInterface {
-    fn add(&mut self, args: Add) -> Result<Bookmark> {
-        serde_json::from_str(&self.request(
-            "POST",
-            "/bookmark",
-            Some(&serde_json::to_string(&args).unwrap()),
-        )?)
-        .map_err(|_| CommandError::SerdeError)
+/// Lists the suggested tags and asks which to add. Each word of the answer is either the number
+/// of a suggestion or a tag to add as is.
+pub fn choose_tags(suggestions: &[TagSuggestion]) -> Vec<String> {
+    if suggestions.is_empty() {
+        println!("No tags to suggest");
+        return Vec::new();
     }
-
-    fn find(&self, args: Search) -> Result<Vec<Bookmark>> {
-        serde_json::from_str(&self.request(
-            "GET",
-            &("/search?".to_string() + &serde_qs::to_string(&args).unwrap()),
-            None,
-        )?)
-        .map_err(|_| CommandError::SerdeError)
-    }
-
-    fn tags(&self, args: Tags) -> Result<Vec<(String, usize)>> {
-        serde_json::from_str(&self.request(
-            "GET",
-            &("/tags?".to_string() + &serde_qs::to_string(&args).unwrap()),
-            None,
-        )?)
-        .map_err(|_| CommandError::SerdeError)
-    }
-
-    fn rename_tag(&self, args: RenameTag) -> Result<usize> {
-        serde_json::from_str(&self.request(
-            "PATCH",
-            &format!("/tags?{}", &serde_qs::to_string(&args).unwrap()),
-            None,
-        )?)
-        .map_err(|_| CommandError::SerdeError)
-    }
-
-    fn edit(&mut self, args: Edit) -> Result<Bookmark> {
-        serde_json::from_str(&self.request(
-            "PUT",
-            "/bookmark",
-            Some(&serde_json::to_string(&args).unwrap()),
-        )?)
-        .map_err(|_| CommandError::SerdeError)
-    }
-
-    fn delete(&self, args: Delete) -> Result<usize> {
-        let bookmarks = serde_json::from_str(&self.request(
-            "GET",
-            &("/search?".to_string() + &serde_qs::to_string(&args).unwrap()),
-            None,
-        )?)
-        .map_err(|_| CommandError::SerdeError)?;
-        eprintln!("in delete");
-        if confirm_delete(&bookmarks) {
-            serde_json::from_str(&self.request(
-                "DELETE",
-                &("/search?".to_string() + &serde_qs::to_string(&args).unwrap()),
-                None,
-            )?)
-            .map_err(|_| CommandError::SerdeError)
-        } else {
-            Ok(0)
-        }
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        println!("{:>2}. {}", i + 1, suggestion.tag);
     }
+    print!("Tags to add (numbers or names separated by spaces, blank for none): ");
+    io::stdout().flush().ok();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok();
+    answer
+        .split_whitespace()
+        .map(|word| match word.parse::<usize>() {
+            Ok(n) if (1..=suggestions.len()).contains(&n) => suggestions[n - 1].tag.clone(),
+            _ => word.to_string(),
+        })
+        .collect()
 }
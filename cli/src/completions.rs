@@ -0,0 +1,379 @@
+//! `syl completions <shell>`: completion scripts generated from the argument definitions. For bash,
+//! zsh and fish, the scripts also ask `syl __complete` for the user's tags and bookmark IDs, passing
+//! along the global flags from the command line so the values come from the same config, profile
+//! and database the command would use.
+
+use std::env;
+use std::io::{self, Write};
+
+use clap_complete::Shell;
+
+use syl_lib::commands::{Interface, Result, Search, Tags};
+
+/// How many of the newest bookmarks to offer when completing an ID
+const MAX_IDS: usize = 50;
+
+/// Values the completion scripts ask for with `syl __complete <kind>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Tags,
+    /// Bookmark IDs, each followed by a tab and the bookmark's title (or URL)
+    Ids,
+}
+
+/// What the completion scripts asked for with `syl __complete <kind> [global flags]`
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub kind: CompletionKind,
+    pub config: Option<String>,
+    pub profile: Option<String>,
+    pub db: Option<String>,
+    pub server: Option<String>,
+    pub local: bool,
+}
+
+/// The request, if syl was run as `syl __complete <kind> [global flags]`
+pub fn request() -> Option<Request> {
+    parse_request(env::args().skip(1))
+}
+
+fn parse_request(mut args: impl Iterator<Item = String>) -> Option<Request> {
+    if args.next()? != "__complete" {
+        return None;
+    }
+    let kind = match args.next()?.as_str() {
+        "tags" => CompletionKind::Tags,
+        "ids" => CompletionKind::Ids,
+        _ => return None,
+    };
+    let mut request = Request {
+        kind,
+        config: None,
+        profile: None,
+        db: None,
+        server: None,
+        local: false,
+    };
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+            None => (arg, None),
+        };
+        let setting = match flag.as_str() {
+            "--config" => &mut request.config,
+            "--profile" => &mut request.profile,
+            "--db" => &mut request.db,
+            "--server" => &mut request.server,
+            "--local" => {
+                request.local = true;
+                continue;
+            }
+            // Whatever else was on the command line doesn't change where values come from
+            _ => continue,
+        };
+        *setting = value.or_else(|| args.next());
+    }
+    Some(request)
+}
+
+pub fn values(interface: &dyn Interface, kind: CompletionKind) -> Result<Vec<String>> {
+    Ok(match kind {
+        CompletionKind::Tags => interface
+            .tags(Tags {
+                sort_by_count: false,
+                reverse: false,
+                tree: false,
+                suggest_merges: false,
+            })?
+            .into_iter()
+            .map(|(tag, _)| tag)
+            .collect(),
+        CompletionKind::Ids => interface
+            .find(Search::default())?
+            .into_iter()
+            .rev()
+            .take(MAX_IDS)
+            .map(|bookmark| {
+                let label = bookmark.title.unwrap_or(bookmark.url);
+                // Tabs separate the ID from its label, and newlines separate values
+                let label = label.replace(['\t', '\n'], " ");
+                format!("{}\t{}", bookmark.id, label)
+            })
+            .collect(),
+    })
+}
+
+/// Completes tags after the tag options and as the arguments of the tag commands, and bookmark IDs
+/// after the commands that take one
+const BASH_DYNAMIC: &str = r#"
+_syl_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    # Global flags are passed along to syl __complete, and the other words that aren't flags are
+    # the command followed by its subcommand and arguments
+    local -a globals=() command=()
+    local i word kind=
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word="${COMP_WORDS[i]}"
+        case "$word" in
+            --local)
+                globals+=("$word")
+                ;;
+            --config|--profile|--db|--server)
+                # Bash splits --flag=value into three words
+                if [[ "${COMP_WORDS[i+1]}" == "=" ]]; then
+                    ((i++))
+                fi
+                globals+=("$word" "${COMP_WORDS[i+1]}")
+                ((i++))
+                ;;
+            -*)
+                ;;
+            *)
+                command+=("$word")
+                ;;
+        esac
+    done
+    case "$prev" in
+        -t|--tag|--tags|--remove-tag|--add|--remove|--into)
+            kind=tags
+            ;;
+        -T)
+            # Only edit's -T removes a tag; add's sets the title
+            if [[ "${command[0]}" == edit || "${command[0]}" == e ]]; then
+                kind=tags
+            fi
+            ;;
+        --id|--ids)
+            kind=ids
+            ;;
+        *)
+            if [[ "$cur" != -* ]]; then
+                case "${command[0]} ${#command[@]}" in
+                    "edit 1"|"e 1"|"show 1"|"read 1"|"unread 1")
+                        kind=ids
+                        ;;
+                    "rename-tag 1")
+                        kind=tags
+                        ;;
+                    "tags "*)
+                        case "${command[1]} ${#command[@]}" in
+                            "show 2"|"edit 2"|"delete 2"|merge*|alias*)
+                                kind=tags
+                                ;;
+                        esac
+                        ;;
+                esac
+            fi
+            ;;
+    esac
+    case "$kind" in
+        tags)
+            COMPREPLY=($(compgen -W "$(syl __complete tags "${globals[@]}" 2>/dev/null)" -- "$cur"))
+            ;;
+        ids)
+            COMPREPLY=($(compgen -W "$(syl __complete ids "${globals[@]}" 2>/dev/null | cut -f1)" -- "$cur"))
+            ;;
+        *)
+            _syl "$@"
+            ;;
+    esac
+}
+
+complete -F _syl_dynamic -o bashdefault -o default syl
+"#;
+
+/// Replaces the generated `_syl` as the entry point, falling back to it
+const ZSH_DYNAMIC: &str = r#"
+_syl() {
+    # Global flags are passed along to syl __complete, and the other words that aren't flags are
+    # the command followed by its subcommand and arguments
+    local -a globals command
+    local i kind
+    for (( i = 2; i < CURRENT; i++ )); do
+        case "${words[i]}" in
+            --local|--config=*|--profile=*|--db=*|--server=*)
+                globals+=("${words[i]}")
+                ;;
+            --config|--profile|--db|--server)
+                globals+=("${words[i]}" "${words[i+1]}")
+                (( i++ ))
+                ;;
+            -*)
+                ;;
+            *)
+                command+=("${words[i]}")
+                ;;
+        esac
+    done
+    case "${words[CURRENT-1]}" in
+        -t|--tag|--tags|--remove-tag|--add|--remove|--into)
+            kind=tags
+            ;;
+        -T)
+            # Only edit's -T removes a tag; add's sets the title
+            [[ "${command[1]}" == (edit|e) ]] && kind=tags
+            ;;
+        --id|--ids)
+            kind=ids
+            ;;
+        *)
+            if [[ "${words[CURRENT]}" != -* ]]; then
+                case "${command[1]} ${#command}" in
+                    "edit 1"|"e 1"|"show 1"|"read 1"|"unread 1")
+                        kind=ids
+                        ;;
+                    "rename-tag 1")
+                        kind=tags
+                        ;;
+                    "tags "*)
+                        case "${command[2]} ${#command}" in
+                            "show 2"|"edit 2"|"delete 2"|merge*|alias*)
+                                kind=tags
+                                ;;
+                        esac
+                        ;;
+                esac
+            fi
+            ;;
+    esac
+    case "$kind" in
+        tags)
+            local -a tags
+            tags=(${(f)"$(syl __complete tags "${globals[@]}" 2>/dev/null)"})
+            _describe -t tags 'tag' tags
+            ;;
+        ids)
+            local -a ids
+            ids=(${${(f)"$(syl __complete ids "${globals[@]}" 2>/dev/null)"}//$'\t'/:})
+            _describe -t ids 'bookmark' ids
+            ;;
+        *)
+            _syl_static "$@"
+            ;;
+    esac
+}
+
+_syl "$@"
+"#;
+
+const FISH_DYNAMIC: &str = r#"
+# Asks syl __complete for values, passing along the global flags on the command line
+function __syl_complete
+    set -l words (commandline -opc)
+    set -l globals
+    for i in (seq 2 (count $words))
+        switch $words[$i]
+            case --local '--config=*' '--profile=*' '--db=*' '--server=*'
+                set -a globals $words[$i]
+            case --config --profile --db --server
+                set -a globals $words[$i] $words[(math $i + 1)]
+        end
+    end
+    syl __complete $argv $globals 2>/dev/null
+end
+
+complete -c syl -s t -l tag -l tags -l remove-tag -l add -l remove -l into -x -a '(__syl_complete tags)'
+complete -c syl -n '__fish_seen_subcommand_from edit e' -s T -x -a '(__syl_complete tags)'
+complete -c syl -l id -l ids -x -a '(__syl_complete ids)'
+complete -c syl -n '__fish_seen_subcommand_from edit e show read unread; and not __fish_seen_subcommand_from tags' -f -a '(__syl_complete ids)'
+complete -c syl -n '__fish_seen_subcommand_from rename-tag' -f -a '(__syl_complete tags)'
+complete -c syl -n '__fish_seen_subcommand_from tags; and __fish_seen_subcommand_from show edit delete merge alias' -f -a '(__syl_complete tags)'
+"#;
+
+pub fn generate(shell: Shell, command: &mut clap::Command, out: &mut dyn Write) -> io::Result<()> {
+    let mut script = Vec::new();
+    clap_complete::generate(shell, command, "syl", &mut script);
+    let mut script = String::from_utf8_lossy(&script).into_owned();
+    match shell {
+        Shell::Bash => script.push_str(BASH_DYNAMIC),
+        Shell::Zsh => {
+            // Keep the generated function under another name, and end with the wrapper instead
+            script = script.replacen("\n_syl() {", "\n_syl_static() {", 1);
+            if let Some(end) = script.rfind("_syl \"$@\"") {
+                script.truncate(end);
+            }
+            script.push_str(ZSH_DYNAMIC.trim_start());
+        }
+        Shell::Fish => script.push_str(FISH_DYNAMIC),
+        _ => {}
+    }
+    out.write_all(script.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(shell: Shell) -> String {
+        let mut command = clap::Command::new("syl")
+            .subcommand(clap::Command::new("show").arg(clap::Arg::new("id")))
+            .subcommand(clap::Command::new("tags").subcommand(clap::Command::new("show")));
+        let mut out = Vec::new();
+        generate(shell, &mut command, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn request(args: &[&str]) -> Option<Request> {
+        parse_request(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_zsh_wrapper() {
+        let script = script(Shell::Zsh);
+        assert!(script.starts_with("#compdef syl\n"));
+        // The generated function is kept under another name, along with the ones it calls
+        assert_eq!(script.matches("\n_syl_static() {").count(), 1);
+        assert!(script.contains("\n_syl__tags_commands() {"));
+        // Only the wrapper is called _syl, and it's only called once, at the very end
+        assert_eq!(script.matches("\n_syl() {").count(), 1);
+        assert_eq!(script.matches("_syl \"$@\"").count(), 1);
+        assert!(script.ends_with(ZSH_DYNAMIC.trim_start()));
+        assert!(script.find("\n_syl_static() {") < script.find("\n_syl() {"));
+    }
+
+    #[test]
+    fn test_appended_scripts() {
+        // The bash wrapper falls back to the generated function, so that keeps its name
+        let bash = script(Shell::Bash);
+        assert!(bash.starts_with("_syl() {"));
+        assert!(bash.ends_with(BASH_DYNAMIC));
+        assert!(script(Shell::Fish).ends_with(FISH_DYNAMIC));
+        // Other shells only get the generated script
+        assert!(!script(Shell::PowerShell).contains("__complete"));
+    }
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(request(&["search", "rust"]), None);
+        assert_eq!(request(&["__complete"]), None);
+        assert_eq!(request(&["__complete", "urls"]), None);
+        let tags = request(&["__complete", "tags"]).unwrap();
+        assert_eq!(tags.kind, CompletionKind::Tags);
+        assert_eq!((tags.config, tags.db, tags.local), (None, None, false));
+
+        let ids = request(&[
+            "__complete",
+            "ids",
+            "--config",
+            "work.toml",
+            "--profile=home",
+            "--db",
+            "a=b.db",
+            "--local",
+            "--verbose",
+            "--server",
+        ])
+        .unwrap();
+        assert_eq!(
+            ids,
+            Request {
+                kind: CompletionKind::Ids,
+                config: Some("work.toml".to_string()),
+                profile: Some("home".to_string()),
+                db: Some("a=b.db".to_string()),
+                server: None,
+                local: true,
+            }
+        );
+    }
+}
@@ -1 +1,5 @@
 pub mod commands;
+pub mod completions;
+pub mod man;
+pub mod pick;
+pub mod tui;
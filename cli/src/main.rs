@@ -1,20 +1,88 @@
-use clap::{Parser, Subcommand};
-
-use syl::commands::ServerInterface;
-use syl_lib::colors::{color, Color};
-use syl_lib::commands::{Add, DatabaseInterface, Delete, Edit, Interface, RenameTag, Search, Tags};
-use syl_lib::config::{Config, ConfigPath};
-use syl_lib::db::Database;
-use syl_lib::util::singular_plural;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+
+use syl::commands::{choose_tags, confirm_delete, load_tag_details};
+use syl::{completions, man, pick, tui};
+use syl_lib::client::ServerInterface;
+use syl_lib::colors::{self, color, Color};
+use syl_lib::commands::{
+    Add, ApplyRules, DatabaseInterface, Delete, DeleteTag, Edit, EditTag, Error, Interface,
+    MarkRead, MergeTags, RenameTag, Retag, Search, SuggestTags, TagAlias, Tags,
+};
+use syl_lib::config::{self, Config, ConfigFile, ConfigPath, Override, Overrides, Server, Source};
+use syl_lib::db::{AddOutcome, Bookmark};
+use syl_lib::storage::{self, OpenError};
+use syl_lib::tags::{self, TagInfo, TagNode};
+use syl_lib::util::{error_chain, format_timestamp, singular_plural};
 use syl_lib::web::WebClient;
 
+const EXIT_CODES: &str = "EXIT STATUS:
+    0  Success
+    1  Nothing was picked
+    2  Invalid arguments or query
+    3  Bookmark or tag not found
+    4  Bookmark already exists
+    5  Server rejected credentials
+    6  Server unreachable
+    7  Database or filesystem error
+    8  Server error or unexpected response
+    9  Invalid configuration
+    10 Bookmark is mirrored from another server and read-only";
+
 #[derive(Parser)]
-#[clap(author, version, about)]
+#[clap(author, version, about, after_help = EXIT_CODES)]
 struct Args {
+    /// Config file to use instead of the default [env: SYL_CONFIG]
+    #[clap(long, global = true, value_parser)]
+    config: Option<String>,
+    /// Profile from the config to use instead of default_profile [env: SYL_PROFILE]
+    #[clap(long, global = true, value_parser)]
+    profile: Option<String>,
+    /// SQLite database to use, instead of the configured server unless syncing with it
+    /// [env: SYL_DB]
+    #[clap(long, global = true, value_parser)]
+    db: Option<String>,
+    /// Server to send commands to, using the credentials under [server] in the config if any
+    /// [env: SYL_SERVER_URL, and SYL_TOKEN for an API token]
+    #[clap(long, global = true, value_parser, conflicts_with = "local")]
+    server: Option<String>,
+    /// Ignore the configured server and use the local database
+    #[clap(long, global = true, action)]
+    local: bool,
     #[clap(subcommand)]
     command: Command,
 }
 
+impl Args {
+    /// The settings given by flags, falling back to the environment
+    fn overrides(&self) -> Overrides {
+        flag_overrides(&self.profile, &self.db, &self.server, self.local)
+    }
+}
+
+/// The settings given by the global flags, falling back to the environment
+fn flag_overrides(
+    profile: &Option<String>,
+    db: &Option<String>,
+    server: &Option<String>,
+    local: bool,
+) -> Overrides {
+    let flag =
+        |value: &Option<String>, origin| value.clone().map(|value| Override { value, origin });
+    Overrides {
+        profile: flag(profile, "--profile"),
+        db_file: flag(db, "--db"),
+        server_url: flag(server, "--server"),
+        token: None,
+        local,
+    }
+    .or(Overrides::from_env())
+}
+
 #[derive(Subcommand)]
 enum Command {
     #[clap(visible_alias = "a")]
@@ -26,35 +94,541 @@ enum Command {
     #[clap(visible_alias = "e")]
     /// Edit bookmarks
     Edit(Edit),
+    /// Show a bookmark in full, with its notes, highlights and history
+    Show {
+        /// ID of the bookmark to show
+        id: i64,
+    },
+    #[clap(visible_alias = "o")]
+    /// Open a bookmark in the browser, by ID or by search
+    Open(OpenArgs),
+    /// Browse and manage bookmarks in a full-screen interface
+    Tui,
+    /// Pick bookmarks with a fuzzy finder and print their URLs, e.g. `xdg-open $(syl pick)`
+    Pick(PickArgs),
     #[clap(visible_alias = "t")]
     /// View/edit tags
-    Tags(Tags),
+    Tags(TagsArgs),
     /// Rename tag
     RenameTag(RenameTag),
+    /// Add and remove tags on every bookmark matching a search
+    Retag(Retag),
+    /// Add a bookmark to the read-later queue (like add --later)
+    Later(Add),
+    /// List the read-later queue, oldest first
+    Queue,
+    /// Mark a bookmark read, taking it out of the queue
+    Read(MarkRead),
+    /// Put a bookmark back in the read-later queue
+    Unread(MarkRead),
+    /// Auto-tagging rules (set with [[rules]] in the config, or the server's config when using a
+    /// server)
+    Rules {
+        #[clap(subcommand)]
+        command: RulesCommand,
+    },
     #[clap(visible_alias = "d")]
-    /// Delete bookmark(s) using the same interface as search
+    /// Delete bookmark(s) by ID, or using the same interface as search
     Delete(Delete),
+    /// List the bookmarks in the trash (where `syl tui` puts them), restore them or empty it
+    Trash {
+        #[clap(subcommand)]
+        command: Option<TrashCommand>,
+    },
+    /// Exchange changes between the local replica and the server (requires `sync = true` under
+    /// [server] in the config)
+    Sync,
+    /// Find, show, create, change or check the config file
+    Config {
+        #[clap(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Print a completion script for a shell, e.g. `syl completions bash >
+    /// /etc/bash_completion.d/syl`
+    Completions {
+        /// The shell to write the script for
+        #[clap(value_parser)]
+        shell: Shell,
+    },
+    /// Print the man page for syl or one of its commands, e.g. `syl man search | man -l -`
+    Man(ManArgs),
+}
+
+#[derive(clap::Args)]
+struct TagsArgs {
+    #[clap(flatten)]
+    list: Tags,
+    #[clap(subcommand)]
+    command: Option<TagCommand>,
+}
+
+#[derive(Subcommand)]
+enum TagCommand {
+    /// Make a tag another name for another tag, retagging bookmarks that use it
+    Alias(TagAlias),
+    /// List tag aliases
+    Aliases,
+    /// Show a tag's details
+    Show {
+        /// The tag to show
+        name: String,
+    },
+    /// Set a tag's description, color or pinned flag
+    Edit(EditTag),
+    /// Remove a tag (and every tag below it) from every bookmark
+    Delete(DeleteTag),
+    /// Rename several tags to one
+    Merge(MergeTags),
+    /// Forget tags no bookmark uses any more
+    Prune,
+}
+
+#[derive(clap::Args)]
+struct OpenArgs {
+    /// ID of the bookmark, or words to search for; the search has to match exactly one bookmark
+    #[clap(required = true)]
+    query: Vec<String>,
+    /// Limit the search to tag(s)
+    #[clap(short, long = "tag", value_parser)]
+    tags: Vec<String>,
+}
+
+#[derive(clap::Args)]
+struct ManArgs {
+    /// The command to document, e.g. `tags alias`; syl itself if not given
+    command: Vec<String>,
+    /// Write pages for syl and all of its commands into this directory instead, e.g.
+    /// /usr/local/share/man/man1
+    #[clap(long, value_parser, conflicts_with = "command")]
+    dir: Option<PathBuf>,
+}
+
+#[derive(clap::Args)]
+struct PickArgs {
+    /// What to start with in the search box
+    query: Vec<String>,
+    /// Only pick from bookmarks with tag(s)
+    #[clap(short, long = "tag", value_parser)]
+    tags: Vec<String>,
+    /// Let Tab pick several bookmarks, printing one per line
+    #[clap(short, long, action)]
+    multi: bool,
+    /// Print the bookmarks' IDs rather than their URLs
+    #[clap(long, action)]
+    id: bool,
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    /// Take bookmark(s) back out of the trash
+    Restore {
+        /// ID(s) of the bookmarks to restore
+        #[clap(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Delete every bookmark in the trash for good
+    Empty {
+        /// Don't ask for confirmation
+        #[clap(short, long, action)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RulesCommand {
+    /// Add the tags from the rules to existing bookmarks
+    Apply(ApplyRules),
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print where the config file is (or would be)
+    Path,
+    /// Show every setting, and whether it's set in the config file or a default
+    Show,
+    /// Write a config file with every setting commented out and explained
+    Init {
+        /// Replace the config file if there is one
+        #[clap(short, long, action)]
+        force: bool,
+    },
+    /// Change a setting in the config file, e.g. `syl config set server.sync true`
+    Set {
+        /// The setting, e.g. db_file or server.url
+        key: String,
+        /// Its new value; timeout is given in seconds
+        value: String,
+    },
+    /// Check the config file for mistakes, such as misspelled keys
+    Validate,
+}
+
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::InvalidQuery(_) => 2,
+        Error::NotFound(_) | Error::TagNotFound(_) | Error::UrlNotFound(_) => 3,
+        Error::DuplicateUrl(_) => 4,
+        Error::ReadOnly { .. } => 10,
+        Error::Unauthorized => 5,
+        Error::Network(_) => 6,
+        Error::Storage(_) | Error::Io(_) => 7,
+        Error::Server(_) | Error::InvalidResponse(_) => 8,
+    }
+}
+
+fn fail(context: &str, error: Error) -> ! {
+    eprintln!("Error {context}: {}", error_chain(&error));
+    process::exit(exit_code(&error));
+}
+
+fn open_local(config: &Config) -> DatabaseInterface {
+    let db = storage::open(config).unwrap_or_else(|e| match e {
+        OpenError::Storage(e) => fail("opening database", Error::Storage(e)),
+        OpenError::Config(e) => {
+            eprintln!("Error finding database: {}", error_chain(&e));
+            process::exit(9);
+        }
+    });
+    DatabaseInterface::from(db, WebClient::new(config.timeout), config.rules.clone())
+}
+
+fn tag_line(node: &TagNode) -> String {
+    format!(
+        "{} ({} {})",
+        color(&node.name, colors::tag_color(&node.path)),
+        node.count,
+        singular_plural("bookmarks", node.count as isize)
+    )
+}
+
+fn print_tag_children(node: &TagNode, prefix: &str) {
+    for (i, child) in node.children.iter().enumerate() {
+        let last = i == node.children.len() - 1;
+        println!(
+            "{prefix}{}{}",
+            if last { "└── " } else { "├── " },
+            tag_line(child)
+        );
+        print_tag_children(
+            child,
+            &format!("{prefix}{}", if last { "    " } else { "│   " }),
+        );
+    }
+}
+
+fn print_tag_info(info: &TagInfo) {
+    println!(
+        "{} ({} {}){}",
+        colors::tag(&info.name),
+        info.count,
+        singular_plural("bookmarks", info.count as isize),
+        if info.pinned { " [pinned]" } else { "" }
+    );
+    if let Some(description) = &info.description {
+        println!("{description}");
+    }
+}
+
+/// Prints a bookmark along with everything `syl show` knows about it
+fn print_bookmark_details(bookmark: &Bookmark) {
+    println!("{bookmark}");
+    if let Some(created_at) = bookmark.created_at {
+        println!("Added: {}", format_timestamp(created_at));
+    }
+    match (bookmark.unread, bookmark.read_at) {
+        (true, _) => println!("Status: unread"),
+        (false, Some(read_at)) => println!("Status: read {}", format_timestamp(read_at)),
+        (false, None) => {}
+    }
+    if let Some(last_visited) = bookmark.last_visited {
+        println!(
+            "Visited: {} {}, last {}",
+            bookmark.visit_count,
+            singular_plural("times", bookmark.visit_count as isize),
+            format_timestamp(last_visited)
+        );
+    }
+    if let Some(origin) = &bookmark.origin {
+        println!("Mirrored from: {origin}");
+    }
+    if let Some(deleted_at) = bookmark.deleted_at {
+        println!("Trashed: {}", format_timestamp(deleted_at));
+    }
+    if let Some(notes) = &bookmark.notes {
+        println!("\n{}\n{notes}", color("Notes", Color::Cyan));
+    }
+    if !bookmark.highlights.is_empty() {
+        println!("\n{}", color("Highlights", Color::Cyan));
+    }
+    for highlight in &bookmark.highlights {
+        println!(
+            "[{}] > {}",
+            color(&highlight.id.to_string(), Color::Blue),
+            highlight.text
+        );
+        if let Some(comment) = &highlight.comment {
+            println!("    {comment}");
+        }
+    }
+}
+
+/// The bookmark `args` picks out, by ID or by a search that has to match exactly one bookmark
+fn find_one(interface: &dyn Interface, args: OpenArgs) -> Bookmark {
+    if let ([query], true) = (args.query.as_slice(), args.tags.is_empty()) {
+        if let Ok(id) = query.parse() {
+            return interface
+                .get(id)
+                .unwrap_or_else(|e| fail("finding bookmark", e));
+        }
+    }
+    let mut bookmarks = interface
+        .find(Search {
+            query: Some(args.query.join(" ")),
+            tags: args.tags,
+            all_tags: false,
+        })
+        .unwrap_or_else(|e| fail("searching bookmarks", e));
+    match bookmarks.len() {
+        0 => {
+            eprintln!("No bookmarks match");
+            process::exit(3);
+        }
+        1 => bookmarks.remove(0),
+        count => {
+            eprintln!("{count} bookmarks match; pick one by ID:");
+            for bookmark in &bookmarks {
+                eprintln!("\n{bookmark}");
+            }
+            process::exit(2);
+        }
+    }
+}
+
+/// Opens the server or, in sync mode or without a server, the local database
+fn open_interface(config: &Config, server: Option<Server>) -> Box<dyn Interface> {
+    // In sync mode, commands work on the local replica so they don't need the server
+    match server {
+        Some(server) if !server.sync => Box::new(ServerInterface::new(server)),
+        _ => Box::new(open_local(config)),
+    }
+}
+
+fn run_config(command: &ConfigCommand, args: &Args) {
+    let config_error = |e: config::Error| -> ! {
+        eprintln!("Error: {}", error_chain(&e));
+        process::exit(9);
+    };
+    let path = ConfigFile::path(ConfigPath::client(args.config.clone()))
+        .unwrap_or_else(|e| config_error(e));
+    match command {
+        ConfigCommand::Path => println!("{}", path.display()),
+        ConfigCommand::Init { force } => match ConfigFile::init(&path, *force) {
+            Ok(()) => println!("Wrote a config template to {}", path.display()),
+            Err(e @ config::Error::Exists(_)) => {
+                eprintln!("Error: {e}; use --force to replace it");
+                process::exit(9);
+            }
+            Err(e) => config_error(e),
+        },
+        ConfigCommand::Show | ConfigCommand::Set { .. } | ConfigCommand::Validate => {
+            let mut file = ConfigFile::open(ConfigPath::Custom(path.display().to_string()))
+                .unwrap_or_else(|e| config_error(e));
+            match command {
+                ConfigCommand::Show => {
+                    file.apply(&args.overrides())
+                        .unwrap_or_else(|e| config_error(e));
+                    match file.contents {
+                        Some(_) => println!("Config file: {}", path.display()),
+                        None => println!("No config file at {}", path.display()),
+                    }
+                    if let Some(profile) = &file.config.profile {
+                        println!("Profile: {profile}");
+                    }
+                    println!();
+                    for setting in file.settings() {
+                        let value = setting.value.as_deref().unwrap_or("(not set)");
+                        let source = match setting.source {
+                            Source::File => color("(config file)", Color::Green),
+                            Source::Default => "(default)".to_string(),
+                            Source::Profile(name) => {
+                                color(&format!("(profile {name})"), Color::Magenta)
+                            }
+                            Source::Override(origin) => {
+                                color(&format!("({origin})"), Color::Yellow)
+                            }
+                        };
+                        println!("{} = {value} {source}", color(setting.key, Color::Cyan));
+                    }
+                    for problem in file.problems() {
+                        eprintln!("Warning: {problem}");
+                    }
+                }
+                ConfigCommand::Set { key, value } => match file.set(key, value) {
+                    Ok(()) => println!("Set {key} in {}", path.display()),
+                    Err(e) => config_error(e),
+                },
+                _ => {
+                    let problems = file.problems();
+                    if problems.is_empty() {
+                        println!("{} is valid", path.display());
+                    } else {
+                        for problem in &problems {
+                            eprintln!("{problem}");
+                        }
+                        process::exit(9);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn write_man(args: &ManArgs) {
+    if let Some(dir) = &args.dir {
+        match man::write_all(&mut Args::command(), dir) {
+            Ok(written) => println!(
+                "Wrote {} man {} to {}",
+                written.len(),
+                singular_plural("pages", written.len() as isize),
+                dir.display()
+            ),
+            Err(e) => {
+                eprintln!("Error writing man pages: {}", error_chain(&e));
+                process::exit(7);
+            }
+        }
+        return;
+    }
+    match man::page(&mut Args::command(), &args.command) {
+        Some(page) => print!("{page}"),
+        None => {
+            eprintln!("Error: no such command: {}", args.command.join(" "));
+            process::exit(2);
+        }
+    }
 }
 
 fn main() {
-    let args = Args::parse();
-    let config = Config::open(ConfigPath::ClientDefault);
-    let mut interface: Box<dyn Interface>;
-    if let Some(server) = config.server {
-        interface = Box::new(ServerInterface::new(server));
-    } else {
-        interface = Box::new(DatabaseInterface::from(
-            Database::open(&config.database()).unwrap(),
-            WebClient::new(config.timeout),
-        ));
+    // `syl __complete <kind>` lists values for the completion scripts. It's handled before clap
+    // so it stays out of the help and the generated scripts.
+    if let Some(request) = completions::request() {
+        // Completion can't show errors, so offer nothing instead
+        let Ok(mut config) = Config::open(ConfigPath::client(request.config)) else {
+            return;
+        };
+        let overrides = flag_overrides(
+            &request.profile,
+            &request.db,
+            &request.server,
+            request.local,
+        );
+        if config.apply(&overrides).is_err() {
+            return;
+        }
+        let server = config.server.take();
+        let interface = open_interface(&config, server);
+        if let Ok(values) = completions::values(interface.as_ref(), request.kind) {
+            for value in values {
+                println!("{value}");
+            }
+        }
+        return;
+    }
+    let mut args = Args::parse();
+    if let Command::Later(add) = &mut args.command {
+        add.unread = true;
+    }
+    if let Command::Completions { shell } = args.command {
+        if let Err(e) = completions::generate(shell, &mut Args::command(), &mut io::stdout()) {
+            eprintln!("Error writing completions: {e}");
+            process::exit(7);
+        }
+        return;
+    }
+    if let Command::Config { command } = &args.command {
+        run_config(command, &args);
+        return;
+    }
+    if let Command::Man(man_args) = &args.command {
+        write_man(man_args);
+        return;
+    }
+    let path = ConfigPath::client(args.config.clone());
+    // Only the default config file is optional; one asked for by name has to be there
+    let required = matches!(path, ConfigPath::Custom(_));
+    let file = ConfigFile::open(path).unwrap_or_else(|e| {
+        eprintln!("Error reading config: {}", error_chain(&e));
+        process::exit(9);
+    });
+    if required && file.contents.is_none() {
+        eprintln!(
+            "Error reading config: {} doesn't exist",
+            file.path.display()
+        );
+        process::exit(9);
+    }
+    let mut config = file.config;
+    if let Err(e) = config.apply(&args.overrides()) {
+        eprintln!("Error reading config: {}", error_chain(&e));
+        process::exit(9);
+    }
+    let server = config.server.take();
+
+    if let Command::Sync = args.command {
+        match server {
+            Some(server) if server.sync => {
+                let mut local = open_local(&config);
+                let rule = server.conflicts;
+                let remote = ServerInterface::new(server);
+                match local.sync(rule, |request| remote.sync(request)) {
+                    Ok(report) => println!(
+                        "Pushed {} {}, pulled {} {}.",
+                        report.pushed,
+                        singular_plural("bookmarks", report.pushed as isize),
+                        report.pulled,
+                        singular_plural("bookmarks", report.pulled as isize)
+                    ),
+                    Err(e) => fail("syncing", e),
+                }
+            }
+            _ => {
+                eprintln!("Error syncing: set sync = true under [server] in the config to sync");
+                process::exit(9);
+            }
+        }
+        return;
     }
+
+    let mut interface = open_interface(&config, server);
     match args.command {
-        Command::Add(args) => match interface.add(args) {
-            Ok(bookmark) => println!("{}", bookmark),
-            Err(e) => eprintln!("Error adding bookmark to database: {:?}", e),
-        },
+        Command::Add(mut args) | Command::Later(mut args) => {
+            let queued = args.unread;
+            if args.suggest {
+                let suggestions = interface
+                    .suggest_tags(SuggestTags {
+                        url: args.url.clone(),
+                    })
+                    .unwrap_or_else(|e| fail("suggesting tags", e));
+                args.tags.extend(choose_tags(&suggestions));
+            }
+            match interface.add(args) {
+                Ok((bookmark, AddOutcome::Created)) => println!("Added bookmark:\n{bookmark}"),
+                Ok((bookmark, AddOutcome::Merged)) => {
+                    println!("A bookmark for that URL already exists; added new tags:\n{bookmark}")
+                }
+                Ok((bookmark, AddOutcome::AlreadyExists)) if queued => println!(
+                    "A bookmark for that URL already exists; added it to the queue:\n{bookmark}"
+                ),
+                Ok((bookmark, AddOutcome::AlreadyExists)) => {
+                    println!("A bookmark for that URL already exists:\n{bookmark}");
+                    process::exit(exit_code(&Error::DuplicateUrl(Box::new(bookmark))));
+                }
+                Err(e) => fail("adding bookmark", e),
+            }
+        }
         Command::Search(args) => match interface.find(args) {
             Ok(bookmarks) => {
+                load_tag_details(interface.as_ref());
                 println!(
                     "Found {} {}.",
                     bookmarks.len(),
@@ -62,37 +636,194 @@ fn main() {
                 );
                 for (i, bookmark) in bookmarks.iter().enumerate() {
                     if i > 0 {
-                        print!("\n");
+                        println!();
                     }
                     println!("{bookmark}");
                 }
             }
-            Err(e) => eprintln!("Error searching database: {:?}", e),
+            Err(e) => fail("searching bookmarks", e),
         },
         Command::Edit(args) => match interface.edit(args) {
             Ok(bookmark) => println!("{bookmark}"),
-            Err(e) => eprintln!("Error editing bookmark: {:?}", e),
+            Err(e) => fail("editing bookmark", e),
+        },
+        Command::Show { id } => match interface.get(id) {
+            Ok(bookmark) => {
+                load_tag_details(interface.as_ref());
+                print_bookmark_details(&bookmark);
+            }
+            Err(e) => fail("showing bookmark", e),
+        },
+        Command::Tui => {
+            if let Err(e) = tui::run(interface.as_mut()) {
+                eprintln!("Error running the interface: {e}");
+                process::exit(7);
+            }
+        }
+        Command::Pick(args) => {
+            let bookmarks = interface
+                .find(Search {
+                    query: None,
+                    tags: args.tags,
+                    all_tags: false,
+                })
+                .unwrap_or_else(|e| fail("listing bookmarks", e));
+            let picked =
+                pick::pick(bookmarks, &args.query.join(" "), args.multi).unwrap_or_else(|e| {
+                    eprintln!("Error running the picker: {e}");
+                    process::exit(7);
+                });
+            if picked.is_empty() {
+                process::exit(1);
+            }
+            for bookmark in picked {
+                if args.id {
+                    println!("{}", bookmark.id);
+                } else {
+                    println!("{}", bookmark.url);
+                }
+            }
+        }
+        Command::Open(args) => {
+            let bookmark = find_one(interface.as_ref(), args);
+            if let Err(e) = webbrowser::open(&bookmark.url) {
+                eprintln!("Error opening {}: {e}", bookmark.url);
+                process::exit(7);
+            }
+            println!("Opened {}", color(&bookmark.url, Color::Cyan));
+            if let Err(e) = interface.visit(bookmark.id) {
+                fail("recording visit", e);
+            }
+        }
+        Command::Tags(TagsArgs {
+            command: Some(TagCommand::Alias(args)),
+            ..
+        }) => {
+            let (alias, tag) = (args.alias.clone(), args.tag.clone());
+            match interface.add_tag_alias(args) {
+                Ok(count) => println!(
+                    "{} is now an alias of {}; retagged {} {}",
+                    colors::tag(&alias),
+                    colors::tag(&tag),
+                    count,
+                    singular_plural("bookmarks", count as isize)
+                ),
+                Err(e) => fail("adding tag alias", e),
+            }
+        }
+        Command::Tags(TagsArgs {
+            command: Some(TagCommand::Aliases),
+            ..
+        }) => match interface.tag_aliases() {
+            Ok(aliases) => {
+                let longest = aliases.iter().map(|a| a.0.len()).max().unwrap_or(0);
+                for (alias, tag) in aliases {
+                    println!("{:longest$} -> {}", colors::tag(&alias), colors::tag(&tag));
+                }
+            }
+            Err(e) => fail("finding tag aliases", e),
+        },
+        Command::Tags(TagsArgs {
+            command: Some(TagCommand::Show { name }),
+            ..
+        }) => match interface.tag_info(&name) {
+            Ok(info) => print_tag_info(&info),
+            Err(e) => fail("finding tag", e),
         },
-        Command::Tags(args) => match interface.tags(args) {
+        Command::Tags(TagsArgs {
+            command: Some(TagCommand::Edit(args)),
+            ..
+        }) => match interface.edit_tag(args) {
+            Ok(info) => print_tag_info(&info),
+            Err(e) => fail("editing tag", e),
+        },
+        Command::Tags(TagsArgs {
+            command: Some(TagCommand::Delete(args)),
+            ..
+        }) => match interface.delete_tag(args) {
+            Ok(count) => println!(
+                "Removed tag from {} {}",
+                count,
+                singular_plural("bookmarks", count as isize)
+            ),
+            Err(e) => fail("deleting tag", e),
+        },
+        Command::Tags(TagsArgs {
+            command: Some(TagCommand::Merge(args)),
+            ..
+        }) => match interface.merge_tags(args) {
+            Ok(count) => println!(
+                "Merged tags on {} {}",
+                count,
+                singular_plural("bookmarks", count as isize)
+            ),
+            Err(e) => fail("merging tags", e),
+        },
+        Command::Tags(TagsArgs {
+            command: Some(TagCommand::Prune),
+            ..
+        }) => match interface.prune_tags() {
+            Ok(count) => println!(
+                "Pruned {} unused {}",
+                count,
+                singular_plural("tags", count as isize)
+            ),
+            Err(e) => fail("pruning tags", e),
+        },
+        Command::Tags(TagsArgs { list: args, .. }) if args.suggest_merges => {
+            match interface.tags(args) {
+                Ok(found) => {
+                    let groups = tags::suggest_merges(&found);
+                    if groups.is_empty() {
+                        println!("No similar tags found.");
+                    }
+                    for group in groups {
+                        let names: Vec<String> = group
+                            .iter()
+                            .map(|(tag, count)| format!("{} ({count})", colors::tag(tag)))
+                            .collect();
+                        println!("{}", names.join(", "));
+                    }
+                }
+                Err(e) => fail("finding tags", e),
+            }
+        }
+        Command::Tags(TagsArgs { list: args, .. }) if args.tree => match interface.tag_tree(args) {
+            Ok(tree) => {
+                for node in &tree {
+                    println!("{}", tag_line(node));
+                    print_tag_children(node, "");
+                }
+            }
+            Err(e) => fail("finding tags", e),
+        },
+        Command::Tags(TagsArgs { list: args, .. }) => match interface.tags(args) {
             Ok(tags) => {
+                let details = load_tag_details(interface.as_ref());
                 println!(
                     "Found {} {}.",
                     tags.len(),
                     singular_plural("tags", tags.len() as isize)
                 );
-                if tags.len() > 0 {
+                if !tags.is_empty() {
                     let longest = tags.iter().map(|t| t.0.len()).max().unwrap();
                     for (tag, count) in tags {
+                        let (pinned, description) = match details.get(&tag) {
+                            Some(info) => (info.pinned, info.description.as_deref()),
+                            None => (false, None),
+                        };
                         println!(
-                            "{:longest$} ({} {})",
-                            color(&tag, Color::Yellow),
+                            "{:longest$} ({} {}){}{}",
+                            colors::tag(&tag),
                             count,
-                            singular_plural("bookmarks", count as isize)
+                            singular_plural("bookmarks", count as isize),
+                            if pinned { " [pinned]" } else { "" },
+                            description.map(|d| format!(" - {d}")).unwrap_or_default()
                         );
                     }
                 }
             }
-            Err(e) => eprintln!("Error finding tags: {:?}", e),
+            Err(e) => fail("finding tags", e),
         },
         Command::RenameTag(args) => match interface.rename_tag(args) {
             Ok(count) => println!(
@@ -100,12 +831,164 @@ fn main() {
                 count,
                 singular_plural("bookmarks", count.try_into().unwrap())
             ),
-            Err(e) => println!("Error: failed to rename tag: {e:?}"),
+            Err(e) => fail("renaming tag", e),
         },
-        Command::Delete(args) => match interface.delete(args) {
-            Ok(0) => println!("No bookmarks deleted."),
-            Ok(count) => println!("Deleted {count} bookmarks"),
-            Err(e) => eprintln!("Error deleting bookmarks: {:?}", e),
+        Command::Retag(args) => match interface.retag(args) {
+            Ok(count) => println!(
+                "Retagged {} {}",
+                count,
+                singular_plural("bookmarks", count as isize)
+            ),
+            Err(e) => fail("retagging bookmarks", e),
         },
+        Command::Queue => match interface.queue() {
+            Ok(bookmarks) => {
+                load_tag_details(interface.as_ref());
+                println!(
+                    "{} unread {}.",
+                    bookmarks.len(),
+                    singular_plural("bookmarks", bookmarks.len() as isize)
+                );
+                for (i, bookmark) in bookmarks.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    println!("{bookmark}");
+                }
+            }
+            Err(e) => fail("listing the queue", e),
+        },
+        Command::Read(args) => match interface.read(args) {
+            Ok(bookmark) => println!("Marked read:\n{bookmark}"),
+            Err(e) => fail("marking bookmark read", e),
+        },
+        Command::Unread(args) => match interface.unread(args) {
+            Ok(bookmark) => println!("Added to the queue:\n{bookmark}"),
+            Err(e) => fail("marking bookmark unread", e),
+        },
+        Command::Rules {
+            command: RulesCommand::Apply(args),
+        } => {
+            let dry_run = args.dry_run;
+            match interface.apply_rules(args) {
+                Ok(matches) => {
+                    for rule_match in &matches {
+                        println!(
+                            "[{}] {}: {}",
+                            color(&rule_match.bookmark.id.to_string(), Color::Blue),
+                            rule_match.bookmark.url,
+                            rule_match
+                                .add_tags
+                                .iter()
+                                .map(|tag| colors::tag(tag))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        );
+                    }
+                    println!(
+                        "{} {} {}",
+                        if dry_run { "Would retag" } else { "Retagged" },
+                        matches.len(),
+                        singular_plural("bookmarks", matches.len() as isize)
+                    );
+                }
+                Err(e) => fail("applying rules", e),
+            }
+        }
+        Command::Delete(args) => {
+            // Both backends delete without asking, so preview what would go and ask here
+            let preview = interface
+                .delete(Delete {
+                    dry_run: true,
+                    ..args.clone()
+                })
+                .unwrap_or_else(|e| fail("finding bookmarks to delete", e));
+            for (i, bookmark) in preview.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("{bookmark}");
+            }
+            let count = preview.len();
+            if count == 0 {
+                println!("No bookmarks deleted.");
+            } else if args.dry_run {
+                println!(
+                    "Would delete {count} {}",
+                    singular_plural("bookmarks", count as isize)
+                );
+            } else if args.force || confirm_delete(count) {
+                // Delete exactly what was shown, even if the search would match more by now
+                let deleted = interface
+                    .delete(Delete {
+                        ids: preview.iter().map(|b| b.id).collect(),
+                        ..Delete::default()
+                    })
+                    .unwrap_or_else(|e| fail("deleting bookmarks", e));
+                println!(
+                    "Deleted {} {}",
+                    deleted.len(),
+                    singular_plural("bookmarks", deleted.len() as isize)
+                );
+            } else {
+                println!("No bookmarks deleted.");
+            }
+        }
+        Command::Trash { command: None } => match interface.trash() {
+            Ok(bookmarks) => {
+                println!(
+                    "{} {} in the trash.",
+                    bookmarks.len(),
+                    singular_plural("bookmarks", bookmarks.len() as isize)
+                );
+                for (i, bookmark) in bookmarks.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    println!("{bookmark}");
+                }
+            }
+            Err(e) => fail("listing the trash", e),
+        },
+        Command::Trash {
+            command: Some(TrashCommand::Restore { ids }),
+        } => {
+            for id in ids {
+                match interface.restore_bookmark(id) {
+                    Ok(bookmark) => println!("Restored:\n{bookmark}"),
+                    Err(e) => fail("restoring bookmark", e),
+                }
+            }
+        }
+        Command::Trash {
+            command: Some(TrashCommand::Empty { force }),
+        } => {
+            let trash = interface
+                .trash()
+                .unwrap_or_else(|e| fail("listing the trash", e));
+            for (i, bookmark) in trash.iter().enumerate() {
+                if i > 0 {
+                    println!();
+                }
+                println!("{bookmark}");
+            }
+            if trash.is_empty() {
+                println!("The trash is already empty.");
+            } else if force || confirm_delete(trash.len()) {
+                let deleted = interface
+                    .empty_trash()
+                    .unwrap_or_else(|e| fail("emptying the trash", e));
+                println!(
+                    "Deleted {} {}",
+                    deleted.len(),
+                    singular_plural("bookmarks", deleted.len() as isize)
+                );
+            } else {
+                println!("No bookmarks deleted.");
+            }
+        }
+        Command::Sync | Command::Config { .. } | Command::Completions { .. } | Command::Man(_) => {
+            unreachable!()
+        }
     };
 }
@@ -0,0 +1,415 @@
+//! `syl man`: roff man pages for syl and each of its commands, generated from the argument
+//! definitions. The page for syl itself also covers the config file and the search query syntax.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::{Arg, Command};
+use roff::{bold, italic, roman, Inline, Roff};
+
+use syl_lib::config;
+
+/// Config sections, listed after the keys from [config::KEYS]
+const CONFIG_SECTIONS: &[(&str, &str)] = &[
+    (
+        "[server]",
+        "Send commands to a syl server rather than using a local database. Needs url, and \
+         username and password or token.",
+    ),
+    (
+        "[[rules]]",
+        "Tags to add automatically to new bookmarks (and to existing ones with syl rules apply). \
+         Each rule has add_tags, a list of tags, and any of: match, words that must all appear \
+         in the URL or title, or site:<host> filters; url, a regex the URL must match; and \
+         title, a regex the title must match.",
+    ),
+    (
+        "[profiles.<name>]",
+        "A named set of settings, used with --profile <name> (or default_profile). A profile can \
+         set any of the settings above except default_profile, e.g. [profiles.work.server]. If it \
+         sets any of db_file, database_url or [server], all three come from the profile. Its \
+         [[rules]] add to the top-level ones.",
+    ),
+];
+
+const CONFIG_EXAMPLE: &str = r#"timeout = { secs = 10, nanos = 0 }
+
+[server]
+url = "https://syl.example.com"
+username = "me"
+password = "hunter2"
+sync = true
+
+[[rules]]
+match = "site:github.com"
+add_tags = ["code"]"#;
+
+/// Environment variables, each taking precedence over the config file and giving way to the flag
+/// of the same name
+const ENVIRONMENT: &[(&str, &str)] = &[
+    (
+        "SYL_CONFIG",
+        "Config file to use instead of the default, like --config.",
+    ),
+    (
+        "SYL_PROFILE",
+        "Profile to use instead of default_profile, like --profile.",
+    ),
+    ("SYL_DB", "SQLite database to use, like --db."),
+    (
+        "SYL_SERVER_URL",
+        "Server to send commands to, like --server.",
+    ),
+    (
+        "SYL_TOKEN",
+        "API token to send to the server in place of the username and password.",
+    ),
+];
+
+/// Each part of a search query, with what it matches
+const QUERY_SYNTAX: &[(&str, &str)] = &[
+    (
+        "words",
+        "Matched as a phrase against the URL, title, description, notes and highlights.",
+    ),
+    (
+        "tag:<tag>",
+        "Only bookmarks with the tag or a tag below it, like --tag.",
+    ),
+    ("is:unread", "Only bookmarks in the read-later queue."),
+    ("is:read", "Only bookmarks not in the read-later queue."),
+];
+
+const FILES: &[(&str, &str)] = &[
+    (
+        "config.toml",
+        "The config file, in the config directory: $XDG_CONFIG_HOME/seeyoulater on Linux, \
+         ~/Library/Application Support/com.phinjensen.seeyoulater on macOS and \
+         %APPDATA%\\phinjensen\\seeyoulater\\config on Windows. The server reads \
+         config-server.toml from the same directory.",
+    ),
+    (
+        "seeyoulater.db",
+        "The default database, in the data directory: $XDG_DATA_HOME/seeyoulater on Linux, \
+         ~/Library/Application Support/com.phinjensen.seeyoulater on macOS and \
+         %APPDATA%\\phinjensen\\seeyoulater\\data on Windows.",
+    ),
+];
+
+/// The visible commands below `command`, without clap's `help`
+fn subcommands<'a, 'help>(command: &'a Command<'help>) -> impl Iterator<Item = &'a Command<'help>> {
+    command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+/// `syl-tags-alias` for the `syl tags alias` command
+fn page_name(path: &[&str]) -> String {
+    path.join("-")
+}
+
+fn section(page: &mut Roff, title: &str) {
+    page.control("SH", [title]);
+}
+
+/// An indented paragraph with `term` above it
+fn entry(page: &mut Roff, term: impl Into<Vec<Inline>>, text: &str) {
+    page.control("TP", []).text(term).text([roman(text)]);
+}
+
+fn preformatted(page: &mut Roff, text: &str) {
+    page.control("RS", []).control("nf", []);
+    for line in text.lines() {
+        page.text([roman(line)]);
+    }
+    page.control("fi", []).control("RE", []);
+}
+
+fn option_term(arg: &Arg) -> Vec<Inline> {
+    let value = arg
+        .get_value_names()
+        .map(|names| names.join(" "))
+        .unwrap_or_else(|| arg.get_id().to_uppercase());
+    if arg.is_positional() {
+        let dots = if arg.is_multiple_values_set() || arg.is_multiple_occurrences_set() {
+            "..."
+        } else {
+            ""
+        };
+        return vec![italic(format!("<{value}>{dots}"))];
+    }
+    let mut flags = Vec::new();
+    if let Some(short) = arg.get_short() {
+        flags.push(format!("-{short}"));
+    }
+    if let Some(long) = arg.get_long() {
+        flags.push(format!("--{long}"));
+    }
+    let mut term = vec![bold(flags.join(", "))];
+    if arg.is_takes_value_set() {
+        term.push(roman(" "));
+        term.push(italic(format!("<{value}>")));
+    }
+    term
+}
+
+fn option_text(arg: &Arg) -> String {
+    let mut text = arg
+        .get_long_help()
+        .or_else(|| arg.get_help())
+        .unwrap_or_default()
+        .to_string();
+    // Flags have the possible values and default of a bool, which aren't worth mentioning
+    if !arg.is_takes_value_set() {
+        return text.trim().to_string();
+    }
+    if let Some(values) = arg.get_value_parser().possible_values() {
+        let values: Vec<_> = values
+            .filter(|value| !value.is_hide_set())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        if !values.is_empty() {
+            text.push_str(&format!(" [possible values: {}]", values.join(", ")));
+        }
+    }
+    let defaults: Vec<_> = arg
+        .get_default_values()
+        .iter()
+        .map(|value| value.to_string_lossy())
+        .collect();
+    if !defaults.is_empty() {
+        text.push_str(&format!(" [default: {}]", defaults.join(", ")));
+    }
+    text.trim().to_string()
+}
+
+/// Renders the page for `command`, found at `path` (e.g. `["syl", "tags", "alias"]`) below `root`
+fn render(root: &Command, command: &Command, path: &[&str]) -> String {
+    let name = page_name(path);
+    let version = root.get_version().unwrap_or_default();
+    let mut page = Roff::new();
+    // The pages aren't dated, but the date's place has to be kept with an empty argument
+    page.control(
+        "TH",
+        [
+            name.to_uppercase().as_str(),
+            "1",
+            "\"\"",
+            &format!("syl {version}"),
+            "syl manual",
+        ],
+    );
+
+    section(&mut page, "NAME");
+    match command.get_about() {
+        Some(about) => page.text([roman(format!("{name} - {about}"))]),
+        None => page.text([roman(name.as_str())]),
+    };
+
+    section(&mut page, "SYNOPSIS");
+    let usage = command.clone().render_usage();
+    let usage = usage.trim_start_matches("USAGE:").trim();
+    page.text([bold(usage)]);
+
+    // Only commands documented beyond their one-line summary get a description
+    if let Some(about) = command.get_long_about() {
+        section(&mut page, "DESCRIPTION");
+        page.text([roman(about)]);
+    }
+
+    let args: Vec<&Arg> = command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .collect();
+    if !args.is_empty() {
+        section(&mut page, "OPTIONS");
+        for arg in args {
+            entry(&mut page, option_term(arg), &option_text(arg));
+        }
+    }
+
+    if subcommands(command).next().is_some() {
+        section(&mut page, "COMMANDS");
+        for sub in subcommands(command) {
+            let sub_path = [path, &[sub.get_name()]].concat();
+            let mut term = vec![bold(sub.get_name())];
+            let aliases: Vec<_> = sub.get_visible_aliases().collect();
+            if !aliases.is_empty() {
+                term.push(roman(format!(" ({})", aliases.join(", "))));
+            }
+            let about = sub.get_about().unwrap_or_default();
+            entry(
+                &mut page,
+                term,
+                &format!("{about}. See {}(1).", page_name(&sub_path)),
+            );
+        }
+    }
+
+    if command.get_arguments().any(|arg| arg.get_id() == "query") || path.len() == 1 {
+        section(&mut page, "QUERY SYNTAX");
+        page.text([roman(
+            "Searches take words and filters, in any order, e.g. syl search async tag:lang/rust \
+             is:unread.",
+        )]);
+        for (term, text) in QUERY_SYNTAX {
+            entry(&mut page, [bold(*term)], text);
+        }
+    }
+
+    if path.len() == 1 {
+        section(&mut page, "CONFIGURATION");
+        page.text([roman(
+            "syl reads its settings from a TOML file, config.toml (see FILES). Every key is \
+             optional, and without a file syl uses a local database. syl config init writes a \
+             commented template, and syl config validate checks the file.",
+        )]);
+        for key in config::KEYS {
+            let text = format!(
+                "{} For example: {} = {}",
+                key.description, key.name, key.example
+            );
+            entry(&mut page, [bold(key.name)], &text);
+        }
+        for (term, text) in CONFIG_SECTIONS {
+            entry(&mut page, [bold(*term)], text);
+        }
+        page.control("PP", []).text([roman("For example:")]);
+        preformatted(&mut page, CONFIG_EXAMPLE);
+
+        section(&mut page, "ENVIRONMENT");
+        page.text([roman(
+            "Environment variables take precedence over the config file, and flags over \
+             environment variables.",
+        )]);
+        for (term, text) in ENVIRONMENT {
+            entry(&mut page, [bold(*term)], text);
+        }
+
+        section(&mut page, "FILES");
+        for (term, text) in FILES {
+            entry(&mut page, [italic(*term)], text);
+        }
+    }
+
+    // Extra help such as the exit codes is written as "HEADING:" followed by indented lines
+    if let Some(after) = command
+        .get_after_long_help()
+        .or_else(|| command.get_after_help())
+    {
+        match after.split_once(":\n") {
+            Some((heading, body)) if heading == heading.to_uppercase() => {
+                section(&mut page, heading);
+                preformatted(
+                    &mut page,
+                    &body.lines().map(str::trim).collect::<Vec<_>>().join("\n"),
+                );
+            }
+            _ => {
+                section(&mut page, "NOTES");
+                page.text([roman(after)]);
+            }
+        }
+    }
+
+    section(&mut page, "SEE ALSO");
+    let mut see_also = Vec::new();
+    if path.len() > 1 {
+        see_also.push(format!("{}(1)", page_name(&path[..path.len() - 1])));
+    }
+    for sub in subcommands(command) {
+        see_also.push(format!(
+            "{}(1)",
+            page_name(&[path, &[sub.get_name()]].concat())
+        ));
+    }
+    if see_also.is_empty() {
+        see_also.push("syl(1)".to_string());
+    }
+    page.text([roman(see_also.join(", "))]);
+
+    page.render()
+}
+
+/// The page for the command at `names` below `root` (e.g. `["tags", "alias"]`), or for `root`
+/// itself if `names` is empty. Returns None if there's no such command.
+pub fn page(root: &mut Command, names: &[String]) -> Option<String> {
+    root.build();
+    let mut path = vec![root.get_name()];
+    let mut command: &Command = root;
+    for name in names {
+        command = command.find_subcommand(name)?;
+        path.push(command.get_name());
+    }
+    Some(render(root, command, &path))
+}
+
+fn write_pages(
+    root: &Command,
+    command: &Command,
+    path: &[&str],
+    dir: &Path,
+    written: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    let file = dir.join(format!("{}.1", page_name(path)));
+    fs::write(&file, render(root, command, path))?;
+    written.push(file);
+    for sub in subcommands(command) {
+        write_pages(root, sub, &[path, &[sub.get_name()]].concat(), dir, written)?;
+    }
+    Ok(())
+}
+
+/// Writes a page for `root` and each command below it into `dir`, returning the files written
+pub fn write_all(root: &mut Command, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    root.build();
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    write_pages(root, root, &[root.get_name()], dir, &mut written)?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root() -> Command<'static> {
+        Command::new("syl")
+            .version("1.0")
+            .subcommand(
+                Command::new("add")
+                    .visible_alias("a")
+                    .about("Add a bookmark")
+                    .arg(Arg::new("url").help("URL to add")),
+            )
+            .subcommand(
+                Command::new("tags")
+                    .about("View/edit tags")
+                    .subcommand(Command::new("alias").about("Make a tag another name")),
+            )
+    }
+
+    fn lookup(names: &[&str]) -> Option<String> {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        page(&mut root(), &names)
+    }
+
+    #[test]
+    fn test_page_lookup() {
+        let syl = lookup(&[]).unwrap();
+        assert!(syl.contains(".TH SYL 1 \"\" \"syl 1.0\""));
+        let alias = lookup(&["tags", "alias"]).unwrap();
+        assert!(alias.contains(".TH SYL-TAGS-ALIAS 1"));
+        assert!(alias.contains("syl\\-tags\\-alias \\- Make a tag another name"));
+        assert!(alias.contains("\\fBsyl tags alias"));
+        // A command's alias finds the page under its real name
+        let add = lookup(&["a"]).unwrap();
+        assert!(add.contains(".TH SYL-ADD 1"));
+        assert!(add.contains("URL to add"));
+
+        assert_eq!(lookup(&["missing"]), None);
+        assert_eq!(lookup(&["tags", "missing"]), None);
+        assert_eq!(lookup(&["add", "alias"]), None);
+    }
+}
@@ -0,0 +1,300 @@
+//! `syl pick`: a fuzzy finder over bookmarks for use in shell commands, e.g.
+//! `xdg-open $(syl pick)`. The picker draws on stderr, so only the picked bookmarks reach stdout.
+
+use std::cmp::Reverse;
+use std::io::{self, Stderr};
+
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout, Position},
+    style::{Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState},
+    Frame, Terminal,
+};
+
+use syl_lib::db::Bookmark;
+
+/// What a bookmark is matched against: its title, URL and tags
+fn candidate(bookmark: &Bookmark) -> String {
+    let mut parts = Vec::new();
+    if let Some(title) = &bookmark.title {
+        parts.push(title.clone());
+    }
+    parts.push(bookmark.url.clone());
+    parts.extend(bookmark.tags.iter().cloned());
+    parts.join("  ")
+}
+
+struct Picker {
+    bookmarks: Vec<Bookmark>,
+    candidates: Vec<String>,
+    matcher: SkimMatcherV2,
+    query: String,
+    /// Indices of the bookmarks matching the query, best first, with the positions of the
+    /// matched characters
+    matches: Vec<(usize, Vec<usize>)>,
+    state: ListState,
+    multi: bool,
+    /// Indices of the bookmarks picked so far with --multi
+    marked: Vec<usize>,
+}
+
+impl Picker {
+    fn new(bookmarks: Vec<Bookmark>, query: &str, multi: bool) -> Self {
+        let mut picker = Picker {
+            candidates: bookmarks.iter().map(candidate).collect(),
+            bookmarks,
+            matcher: SkimMatcherV2::default(),
+            query: query.to_string(),
+            matches: Vec::new(),
+            state: ListState::default(),
+            multi,
+            marked: Vec::new(),
+        };
+        picker.update_matches();
+        picker
+    }
+
+    fn update_matches(&mut self) {
+        let query = self.query.trim();
+        self.matches = if query.is_empty() {
+            (0..self.candidates.len())
+                .map(|i| (i, Vec::new()))
+                .collect()
+        } else {
+            let mut scored: Vec<(i64, usize, Vec<usize>)> = self
+                .candidates
+                .iter()
+                .enumerate()
+                .filter_map(|(i, candidate)| {
+                    let (score, indices) = self.matcher.fuzzy_indices(candidate, query)?;
+                    Some((score, i, indices))
+                })
+                .collect();
+            // Stable, so equally good matches keep their order
+            scored.sort_by_key(|(score, ..)| Reverse(*score));
+            scored
+                .into_iter()
+                .map(|(_, i, indices)| (i, indices))
+                .collect()
+        };
+        self.state.select((!self.matches.is_empty()).then_some(0));
+    }
+
+    fn current(&self) -> Option<usize> {
+        self.state
+            .selected()
+            .and_then(|i| self.matches.get(i))
+            .map(|(i, _)| *i)
+    }
+
+    /// Handles a key press, returning the picked bookmarks' indices once the user is done (empty
+    /// if they gave up)
+    fn handle_key(&mut self, key: KeyEvent) -> Option<Vec<usize>> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Some(Vec::new()),
+            KeyCode::Char('c') if ctrl => return Some(Vec::new()),
+            KeyCode::Enter => {
+                if self.multi && !self.marked.is_empty() {
+                    return Some(std::mem::take(&mut self.marked));
+                }
+                return self.current().map(|i| vec![i]);
+            }
+            KeyCode::Up => self.state.select_previous(),
+            KeyCode::Char('p') if ctrl => self.state.select_previous(),
+            KeyCode::Down => self.state.select_next(),
+            KeyCode::Char('n') if ctrl => self.state.select_next(),
+            KeyCode::Tab if self.multi => {
+                if let Some(i) = self.current() {
+                    match self.marked.iter().position(|&m| m == i) {
+                        Some(position) => {
+                            self.marked.remove(position);
+                        }
+                        None => self.marked.push(i),
+                    }
+                    self.state.select_next();
+                }
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.update_matches();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.update_matches();
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [input, list] =
+            Layout::vertical([Constraint::Length(1), Constraint::Fill(1)]).areas(frame.area());
+        let mut prompt = vec![Span::raw("> ").cyan(), Span::raw(self.query.as_str())];
+        let count = format!(
+            "  {}/{}{}",
+            self.matches.len(),
+            self.bookmarks.len(),
+            if self.multi && !self.marked.is_empty() {
+                format!(" ({} picked)", self.marked.len())
+            } else {
+                String::new()
+            }
+        );
+        prompt.push(Span::raw(count).dark_gray());
+        frame.render_widget(Line::from(prompt), input);
+        frame.set_cursor_position(Position::new(
+            input.x + 2 + self.query.chars().count() as u16,
+            input.y,
+        ));
+
+        let items = self.matches.iter().map(|(i, indices)| {
+            let marker = if self.marked.contains(i) {
+                "• "
+            } else {
+                "  "
+            };
+            let mut spans = vec![Span::raw(marker).magenta()];
+            // Bold the characters the query matched
+            spans.extend(self.candidates[*i].chars().enumerate().map(|(n, c)| {
+                if indices.contains(&n) {
+                    Span::raw(c.to_string()).bold().yellow()
+                } else {
+                    Span::raw(c.to_string())
+                }
+            }));
+            ListItem::new(Line::from(spans))
+        });
+        let list_widget =
+            List::new(items).highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list_widget, list, &mut self.state);
+    }
+}
+
+fn run_picker(
+    terminal: &mut Terminal<CrosstermBackend<Stderr>>,
+    picker: &mut Picker,
+) -> io::Result<Vec<usize>> {
+    loop {
+        terminal.draw(|frame| picker.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                if let Some(picked) = picker.handle_key(key) {
+                    return Ok(picked);
+                }
+            }
+        }
+    }
+}
+
+/// Lets the user pick from `bookmarks`, starting with `query` typed in. With `multi`, Tab marks
+/// several bookmarks to pick at once. Returns nothing if the user gives up.
+pub fn pick(bookmarks: Vec<Bookmark>, query: &str, multi: bool) -> io::Result<Vec<Bookmark>> {
+    let mut picker = Picker::new(bookmarks, query, multi);
+
+    terminal::enable_raw_mode()?;
+    execute!(io::stderr(), EnterAlternateScreen)?;
+    let result = Terminal::new(CrosstermBackend::new(io::stderr()))
+        .and_then(|mut terminal| run_picker(&mut terminal, &mut picker));
+    execute!(io::stderr(), LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+
+    let picked = result?;
+    Ok(picked
+        .into_iter()
+        .map(|i| picker.bookmarks[i].clone())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(id: i64, title: &str) -> Bookmark {
+        Bookmark {
+            id,
+            url: format!("example.org/{id}"),
+            title: Some(title.to_string()),
+            description: None,
+            tags: Vec::new(),
+            origin: None,
+            unread: false,
+            read_at: None,
+            notes: None,
+            highlights: Vec::new(),
+            created_at: None,
+            last_visited: None,
+            visit_count: 0,
+            deleted_at: None,
+        }
+    }
+
+    fn new_picker(multi: bool) -> Picker {
+        let titles = ["Rambling untidy stories told", "Rust", "Python"];
+        let bookmarks = (0..).zip(titles).map(|(id, t)| bookmark(id, t)).collect();
+        Picker::new(bookmarks, "", multi)
+    }
+
+    fn press(picker: &mut Picker, code: KeyCode) -> Option<Vec<usize>> {
+        picker.handle_key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn matched(picker: &Picker) -> Vec<usize> {
+        picker.matches.iter().map(|(i, _)| *i).collect()
+    }
+
+    #[test]
+    fn test_ranking() {
+        let mut picker = new_picker(false);
+        assert_eq!(matched(&picker), vec![0, 1, 2]);
+        for c in "rust".chars() {
+            press(&mut picker, KeyCode::Char(c));
+        }
+        // The whole word beats the same letters scattered, and Python doesn't match at all
+        assert_eq!(matched(&picker), vec![1, 0]);
+        assert_eq!(picker.matches[0].1, vec![0, 1, 2, 3]);
+        assert_eq!(picker.current(), Some(1));
+        press(&mut picker, KeyCode::Backspace);
+        assert_eq!(picker.query, "rus");
+    }
+
+    #[test]
+    fn test_multi_select() {
+        let mut picker = new_picker(true);
+        assert_eq!(press(&mut picker, KeyCode::Tab), None);
+        press(&mut picker, KeyCode::Tab);
+        assert_eq!(picker.marked, vec![0, 1]);
+        // Tab again on a picked bookmark unpicks it
+        press(&mut picker, KeyCode::Up);
+        press(&mut picker, KeyCode::Tab);
+        assert_eq!(picker.marked, vec![0]);
+        assert_eq!(press(&mut picker, KeyCode::Enter), Some(vec![0]));
+
+        // Without --multi, Tab does nothing and Enter picks the highlighted bookmark
+        let mut picker = new_picker(false);
+        press(&mut picker, KeyCode::Tab);
+        press(&mut picker, KeyCode::Down);
+        assert!(picker.marked.is_empty());
+        assert_eq!(press(&mut picker, KeyCode::Enter), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_enter_without_matches() {
+        let mut picker = new_picker(false);
+        press(&mut picker, KeyCode::Char('z'));
+        assert!(picker.matches.is_empty());
+        assert_eq!(picker.current(), None);
+        // Nothing to pick, so the picker stays open
+        assert_eq!(press(&mut picker, KeyCode::Enter), None);
+        assert_eq!(press(&mut picker, KeyCode::Esc), Some(Vec::new()));
+    }
+}
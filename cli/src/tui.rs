@@ -0,0 +1,524 @@
+//! `syl tui`: a full-screen interface for browsing bookmarks. The tag sidebar filters the list, the
+//! search box narrows it as you type, and the detail pane shows the selected bookmark in full.
+
+use std::io::{self, Write};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout, Position, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+    DefaultTerminal, Frame,
+};
+
+use syl_lib::{
+    colors,
+    commands::{Edit, Interface, MarkRead, Result, Search, Tags},
+    db::Bookmark,
+    util::{error_chain, format_timestamp, singular_plural},
+};
+
+use crate::commands::load_tag_details;
+
+const HELP: &str =
+    "/ search  tab tags  o open  t tags  r read/unread  d trash  u undo  y copy URL  q quit";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Bookmarks,
+    Tags,
+}
+
+enum Mode {
+    Normal,
+    /// Typing in the search box
+    Search,
+    /// Typing tags to add, or to remove with a leading -
+    EditTags(String),
+}
+
+struct App<'a> {
+    interface: &'a mut dyn Interface,
+    query: String,
+    /// The tag the list is limited to, if any
+    tag: Option<String>,
+    bookmarks: Vec<Bookmark>,
+    tags: Vec<(String, usize)>,
+    bookmark_state: ListState,
+    /// The sidebar's first entry is every tag
+    tag_state: ListState,
+    focus: Focus,
+    mode: Mode,
+    /// Feedback from the last action, shown in place of the help line
+    message: Option<String>,
+    /// Bookmarks moved to the trash, most recent last, for u to restore
+    trashed: Vec<i64>,
+}
+
+fn tag_color(name: &str) -> Color {
+    match colors::tag_color(name) {
+        colors::Color::Red => Color::Red,
+        colors::Color::Green | colors::Color::BoldGreen => Color::Green,
+        colors::Color::Yellow => Color::Yellow,
+        colors::Color::Blue => Color::Blue,
+        colors::Color::Magenta => Color::Magenta,
+        colors::Color::Cyan => Color::Cyan,
+        colors::Color::White => Color::White,
+    }
+}
+
+fn tag_spans(tags: &[String]) -> Vec<Span<'static>> {
+    tags.iter()
+        .flat_map(|tag| [Span::raw(" "), Span::raw(tag.clone()).fg(tag_color(tag))])
+        .collect()
+}
+
+/// Copies `text` to the clipboard with an OSC 52 escape sequence, which most terminals support
+/// (including over SSH)
+fn copy_to_clipboard(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
+
+impl<'a> App<'a> {
+    fn new(interface: &'a mut dyn Interface) -> Self {
+        let mut app = App {
+            interface,
+            query: String::new(),
+            tag: None,
+            bookmarks: Vec::new(),
+            tags: Vec::new(),
+            bookmark_state: ListState::default().with_selected(Some(0)),
+            tag_state: ListState::default().with_selected(Some(0)),
+            focus: Focus::Bookmarks,
+            mode: Mode::Normal,
+            message: None,
+            trashed: Vec::new(),
+        };
+        app.reload_tags();
+        if app.message.is_none() {
+            load_tag_details(app.interface);
+        }
+        app.reload();
+        app
+    }
+
+    /// Shows the outcome of an action in the status line
+    fn report<T>(&mut self, result: Result<T>, success: impl FnOnce(T) -> String) {
+        self.message = Some(match result {
+            Ok(value) => success(value),
+            Err(e) => format!("Error: {}", error_chain(&e)),
+        });
+    }
+
+    /// Runs the search again, keeping the selection in range
+    fn reload(&mut self) {
+        let search = Search {
+            query: Some(self.query.trim().to_string()).filter(|query| !query.is_empty()),
+            tags: self.tag.iter().cloned().collect(),
+            all_tags: false,
+        };
+        match self.interface.find(search) {
+            Ok(bookmarks) => self.bookmarks = bookmarks,
+            Err(e) => self.message = Some(format!("Error: {}", error_chain(&e))),
+        }
+        let last = self.bookmarks.len().saturating_sub(1);
+        if self.bookmark_state.selected().is_none_or(|i| i > last) {
+            self.bookmark_state.select(Some(last));
+        }
+    }
+
+    fn reload_tags(&mut self) {
+        let args = Tags {
+            sort_by_count: false,
+            reverse: false,
+            tree: false,
+            suggest_merges: false,
+        };
+        match self.interface.tags(args) {
+            Ok(tags) => self.tags = tags,
+            Err(e) => self.message = Some(format!("Error: {}", error_chain(&e))),
+        }
+        if self
+            .tag_state
+            .selected()
+            .is_none_or(|i| i > self.tags.len())
+        {
+            self.tag_state.select(Some(0));
+        }
+    }
+
+    fn selected(&self) -> Option<&Bookmark> {
+        self.bookmark_state
+            .selected()
+            .and_then(|i| self.bookmarks.get(i))
+    }
+
+    /// Handles a key press, returning false once it's time to quit
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+        match &mut self.mode {
+            Mode::Normal => return self.handle_normal_key(key.code),
+            Mode::Search => match key.code {
+                KeyCode::Enter | KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.reload();
+                }
+                KeyCode::Char(c) => {
+                    self.query.push(c);
+                    self.reload();
+                }
+                _ => {}
+            },
+            Mode::EditTags(input) => match key.code {
+                KeyCode::Enter => {
+                    let input = std::mem::take(input);
+                    self.mode = Mode::Normal;
+                    self.edit_tags(&input);
+                }
+                KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            },
+        }
+        true
+    }
+
+    fn handle_normal_key(&mut self, code: KeyCode) -> bool {
+        self.message = None;
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.focus = match self.focus {
+                    Focus::Bookmarks => Focus::Tags,
+                    Focus::Tags => Focus::Bookmarks,
+                }
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.focused_state().select_next(),
+            KeyCode::Up | KeyCode::Char('k') => self.focused_state().select_previous(),
+            KeyCode::Home | KeyCode::Char('g') => self.focused_state().select_first(),
+            KeyCode::End | KeyCode::Char('G') => self.focused_state().select_last(),
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Enter if self.focus == Focus::Tags => self.filter_by_selected_tag(),
+            KeyCode::Enter | KeyCode::Char('o') => self.open(),
+            KeyCode::Char('t') if self.selected().is_some() => {
+                self.mode = Mode::EditTags(String::new())
+            }
+            KeyCode::Char('r') => self.toggle_read(),
+            KeyCode::Char('d') => self.trash(),
+            KeyCode::Char('u') => self.restore(),
+            KeyCode::Char('y') => {
+                if let Some(url) = self.selected().map(|b| b.url.clone()) {
+                    let result = copy_to_clipboard(&url);
+                    self.message = Some(match result {
+                        Ok(()) => format!("Copied {url}"),
+                        Err(e) => format!("Error copying URL: {e}"),
+                    });
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn focused_state(&mut self) -> &mut ListState {
+        match self.focus {
+            Focus::Bookmarks => &mut self.bookmark_state,
+            Focus::Tags => &mut self.tag_state,
+        }
+    }
+
+    fn filter_by_selected_tag(&mut self) {
+        self.tag = match self.tag_state.selected() {
+            Some(i) if i > 0 => self.tags.get(i - 1).map(|(tag, _)| tag.clone()),
+            _ => None,
+        };
+        self.bookmark_state.select(Some(0));
+        self.focus = Focus::Bookmarks;
+        self.reload();
+    }
+
+    fn open(&mut self) {
+        let Some(bookmark) = self.selected().cloned() else {
+            return;
+        };
+        if let Err(e) = webbrowser::open(&bookmark.url) {
+            self.message = Some(format!("Error opening {}: {e}", bookmark.url));
+            return;
+        }
+        let result = self.interface.visit(bookmark.id);
+        self.report(result, |_| format!("Opened {}", bookmark.url));
+        self.reload();
+    }
+
+    fn edit_tags(&mut self, input: &str) {
+        let Some(id) = self.selected().map(|b| b.id) else {
+            return;
+        };
+        let (remove_tags, add_tags): (Vec<String>, Vec<String>) = input
+            .split_whitespace()
+            .map(str::to_string)
+            .partition(|tag| tag.starts_with('-'));
+        let result = self.interface.edit(Edit {
+            id,
+            add_tags,
+            remove_tags: remove_tags.iter().map(|tag| tag[1..].to_string()).collect(),
+            notes: None,
+            highlight: None,
+            comment: None,
+            remove_highlights: Vec::new(),
+        });
+        self.report(result, |_| "Updated tags".to_string());
+        self.reload_tags();
+        self.reload();
+    }
+
+    fn toggle_read(&mut self) {
+        let Some(bookmark) = self.selected() else {
+            return;
+        };
+        let (id, unread) = (bookmark.id, bookmark.unread);
+        let args = MarkRead {
+            id: Some(id),
+            url: None,
+        };
+        let result = if unread {
+            self.interface.read(args)
+        } else {
+            self.interface.unread(args)
+        };
+        self.report(result, |bookmark| {
+            if bookmark.unread {
+                "Added to the queue".to_string()
+            } else {
+                "Marked read".to_string()
+            }
+        });
+        self.reload();
+    }
+
+    fn trash(&mut self) {
+        let Some(id) = self.selected().map(|b| b.id) else {
+            return;
+        };
+        let result = self.interface.trash_bookmark(id);
+        if result.is_ok() {
+            self.trashed.push(id);
+        }
+        self.report(result, |bookmark| {
+            format!("Moved {} to the trash (u to undo)", bookmark.url)
+        });
+        self.reload_tags();
+        self.reload();
+    }
+
+    /// Takes the most recently trashed bookmark back out of the trash
+    fn restore(&mut self) {
+        let Some(id) = self.trashed.pop() else {
+            self.message = Some("Nothing to restore".to_string());
+            return;
+        };
+        let result = self.interface.restore_bookmark(id);
+        self.report(result, |bookmark| format!("Restored {}", bookmark.url));
+        self.reload_tags();
+        self.reload();
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, search, status] = Layout::vertical([
+            Constraint::Fill(1),
+            Constraint::Length(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [sidebar, list, detail] = Layout::horizontal([
+            Constraint::Length(24),
+            Constraint::Fill(2),
+            Constraint::Fill(3),
+        ])
+        .areas(main);
+        self.draw_tags(frame, sidebar);
+        self.draw_bookmarks(frame, list);
+        self.draw_detail(frame, detail);
+
+        let searching = matches!(self.mode, Mode::Search);
+        frame.render_widget(
+            Paragraph::new(self.query.as_str()).block(
+                Block::bordered()
+                    .title("Search")
+                    .border_style(border_style(searching)),
+            ),
+            search,
+        );
+        if searching {
+            frame.set_cursor_position(Position::new(
+                search.x + 1 + self.query.chars().count() as u16,
+                search.y + 1,
+            ));
+        }
+
+        let status_line = match &self.mode {
+            Mode::EditTags(input) => {
+                let prompt = "Tags to add (-tag to remove): ";
+                frame.set_cursor_position(Position::new(
+                    status.x + (prompt.chars().count() + input.chars().count()) as u16,
+                    status.y,
+                ));
+                Line::from(format!("{prompt}{input}"))
+            }
+            _ => match &self.message {
+                Some(message) => Line::from(message.as_str()),
+                None => Line::from(HELP).dark_gray(),
+            },
+        };
+        frame.render_widget(status_line, status);
+    }
+
+    fn draw_tags(&mut self, frame: &mut Frame, area: Rect) {
+        let all = ListItem::new("All bookmarks");
+        let items = std::iter::once(all).chain(self.tags.iter().map(|(tag, count)| {
+            let line = Line::from(vec![
+                Span::raw(tag.clone()).fg(tag_color(tag)),
+                Span::raw(format!(" ({count})")).dark_gray(),
+            ]);
+            ListItem::new(line)
+        }));
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title("Tags")
+                    .border_style(border_style(self.focus == Focus::Tags)),
+            )
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.tag_state);
+    }
+
+    fn draw_bookmarks(&mut self, frame: &mut Frame, area: Rect) {
+        let items = self.bookmarks.iter().map(|bookmark| {
+            let mut spans = vec![Span::raw(
+                bookmark
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| bookmark.url.clone()),
+            )];
+            if bookmark.unread {
+                spans.push(Span::raw(" •").magenta());
+            }
+            spans.extend(tag_spans(&bookmark.tags));
+            ListItem::new(Line::from(spans))
+        });
+        let title = match &self.tag {
+            Some(tag) => format!("Bookmarks in {tag} ({})", self.bookmarks.len()),
+            None => format!("Bookmarks ({})", self.bookmarks.len()),
+        };
+        let list = List::new(items)
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .border_style(border_style(self.focus == Focus::Bookmarks)),
+            )
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.bookmark_state);
+    }
+
+    fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = Vec::new();
+        if let Some(bookmark) = self.selected() {
+            if let Some(title) = &bookmark.title {
+                lines.push(Line::from(title.as_str()).bold().green());
+            }
+            lines.push(Line::from(bookmark.url.as_str()).cyan());
+            if !bookmark.tags.is_empty() {
+                lines.push(Line::from(tag_spans(&bookmark.tags)));
+            }
+            if let Some(description) = &bookmark.description {
+                lines.push(Line::default());
+                lines.push(Line::from(description.as_str()));
+            }
+            lines.push(Line::default());
+            if let Some(created_at) = bookmark.created_at {
+                lines.push(Line::from(format!(
+                    "Added: {}",
+                    format_timestamp(created_at)
+                )));
+            }
+            match (bookmark.unread, bookmark.read_at) {
+                (true, _) => lines.push(Line::from("Status: unread").magenta()),
+                (false, Some(read_at)) => lines.push(Line::from(format!(
+                    "Status: read {}",
+                    format_timestamp(read_at)
+                ))),
+                (false, None) => {}
+            }
+            if let Some(last_visited) = bookmark.last_visited {
+                lines.push(Line::from(format!(
+                    "Visited: {} {}, last {}",
+                    bookmark.visit_count,
+                    singular_plural("times", bookmark.visit_count as isize),
+                    format_timestamp(last_visited)
+                )));
+            }
+            if let Some(origin) = &bookmark.origin {
+                lines.push(Line::from(format!("Mirrored from: {origin}")));
+            }
+            if let Some(notes) = &bookmark.notes {
+                lines.push(Line::default());
+                lines.push(Line::from("Notes").cyan());
+                lines.extend(notes.lines().map(|line| Line::from(line.to_string())));
+            }
+            if !bookmark.highlights.is_empty() {
+                lines.push(Line::default());
+                lines.push(Line::from("Highlights").cyan());
+            }
+            for highlight in &bookmark.highlights {
+                lines.push(Line::from(format!("> {}", highlight.text)));
+                if let Some(comment) = &highlight.comment {
+                    lines.push(Line::from(format!("  {comment}")).dark_gray());
+                }
+            }
+        }
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title("Details")),
+            area,
+        );
+    }
+}
+
+fn border_style(focused: bool) -> Style {
+    if focused {
+        Style::new().cyan()
+    } else {
+        Style::new()
+    }
+}
+
+fn run_app(terminal: &mut DefaultTerminal, app: &mut App) -> io::Result<()> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press && !app.handle_key(key) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Runs the interface until the user quits
+pub fn run(interface: &mut dyn Interface) -> io::Result<()> {
+    let mut app = App::new(interface);
+    let mut terminal = ratatui::init();
+    let result = run_app(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
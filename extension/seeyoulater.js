@@ -11,7 +11,7 @@ browser.runtime.onMessage.addListener(
       "password",
     ]);
     try {
-      let response = await fetch(server_url + "/add", {
+      let response = await fetch(server_url + "/bookmark", {
         method: "POST",
         headers: {
           "Content-Type": "application/json",
@@ -23,14 +23,25 @@ browser.runtime.onMessage.addListener(
           url,
           description,
           tags,
+          merge: true,
         }),
       });
       let body = await response.json();
+      let message;
+      if (response.status === 201) {
+        message = "Saved bookmark successfully";
+      } else if (response.status === 200) {
+        message = "Already bookmarked, tags updated";
+      } else if (body.code === "duplicate_url") {
+        message = "Already bookmarked";
+      } else {
+        throw new Error(body.message || `Server responded with ${response.status}`);
+      }
       await browser.notifications.clear(notificationId);
       notificationId = await browser.notifications.create({
         type: "basic",
         title: "See You Later",
-        message: `Saved bookmark successfully`,
+        message,
       });
     } catch (err) {
       await browser.notifications.clear(notificationId);
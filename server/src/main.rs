@@ -1,77 +1,382 @@
 #[macro_use]
 extern crate rouille;
 
-use std::io;
 use std::sync::Mutex;
+use std::time::Duration;
+use std::{env, io, process, thread};
+
+use clap::{Parser, Subcommand};
+use rouille::{Request, Response};
+use syl_lib::api::{ApiError, ErrorCode};
+use syl_lib::client::ServerInterface;
 use syl_lib::commands::DatabaseInterface;
-use syl_lib::config::{Config, ConfigPath};
-use syl_lib::db::Database;
+use syl_lib::config::{self, Config, ConfigPath, Server};
+use syl_lib::storage;
+use syl_lib::users;
+use syl_lib::util::{error_chain, singular_plural};
 use syl_lib::web::WebClient;
-use syl_server::routes::{add, delete, edit, rename_tag, search, tags};
+use syl_server::routes::{
+    add, add_tag_alias, apply_rules, changes, delete, delete_bookmark, delete_tag, edit, edit_tag,
+    empty_trash, error_response, get, merge_tags, prune_tags, queue, read, rename_tag,
+    restore_bookmark, retag, search, suggest_tags, sync, tag_aliases, tag_details, tag_info,
+    tag_name, tag_tree, tags, trash, trash_bookmark, unread, visit,
+};
 
-fn main() {
-    let config = Config::open(ConfigPath::ServerDefault);
-
-    match &config.server {
-        Some(server) => println!("Now listening on {}", server.url),
-        None => panic!("[server] section must be defined in config!"),
-    };
-
-    let interface = Mutex::new(DatabaseInterface::from(
-        Database::open(&config.database()).unwrap(),
-        WebClient::new(config.timeout),
-    ));
-    let server = config.server.unwrap();
-
-    rouille::start_server(&server.url, move |request| {
-        rouille::log(&request, io::stdout(), || {
-            if request.method() == "OPTIONS" {
-                rouille::Response::empty_204()
-                    .with_additional_header("Access-Control-Allow-Origin", "*")
-                    .with_additional_header(
-                        "Access-Control-Allow-Methods",
-                        "POST, GET, DELETE, OPTIONS",
-                    )
-                    .with_additional_header(
-                        "Access-Control-Allow-Headers",
-                        "content-type, x-username, x-password",
-                    )
-                    .with_additional_header("Access-Control-Max-Age", "86400")
-            } else if request.header("X-Username").is_none()
-                || request.header("X-Password").is_none()
-            {
-                rouille::Response::text("X-Username and X-Password headers required")
-                    .with_status_code(401)
-            } else {
-                let username = request.header("X-Username").unwrap();
-                let password = request.header("X-Password").unwrap();
-                if username != &server.username || password != &server.password {
-                    rouille::Response::text("Username or password incorrect").with_status_code(401)
-                } else {
-                    router!(request,
-                        (POST) (/bookmark) => {
-                            add(&mut interface.lock().unwrap(), request)
-                        },
-                        (PUT) (/bookmark) => {
-                            edit(&mut interface.lock().unwrap(), request)
-                        },
-                        (GET) (/search) => {
-                            search(&mut interface.lock().unwrap(), request)
-                        },
-                        (DELETE) (/search) => {
-                            delete(&mut interface.lock().unwrap(), request)
-                        },
-                        (GET) (/tags) => {
-                            tags(&mut interface.lock().unwrap(), request)
-                        },
-                        (PATCH) (/tags) => {
-                            rename_tag(&mut interface.lock().unwrap(), request)
-                        },
-                        _ => rouille::Response::empty_404()
-                    )
-                    .with_additional_header("Access-Control-Allow-Origin", "*")
-                }
-            }
+#[derive(Parser)]
+#[clap(author, version, about)]
+struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the HTTP API (the default)
+    Serve,
+    /// Keep copying another server's bookmarks into this server's database, where they're
+    /// read-only
+    Mirror(Mirror),
+    /// Manage the users who can sign in, besides the one in the [server] section
+    #[clap(subcommand)]
+    Users(Users),
+}
+
+#[derive(Subcommand)]
+enum Users {
+    /// Add a user, reading their password from the first line of standard input
+    Add {
+        /// The name to sign in with
+        username: String,
+    },
+    /// List the users
+    List,
+    /// Remove a user
+    Remove {
+        /// The user to remove
+        username: String,
+    },
+}
+
+#[derive(clap::Args)]
+struct Mirror {
+    /// URL of the server to mirror
+    #[clap(long, value_parser)]
+    from: String,
+    /// API token for the server being mirrored (its [server] token setting). Defaults to
+    /// SYL_TOKEN, which keeps it out of the process list and shell history.
+    #[clap(long, value_parser)]
+    token: Option<String>,
+    /// Only mirror bookmarks with this tag or one below it
+    #[clap(long, value_parser)]
+    tag: Option<String>,
+    /// Seconds to wait between checks for new changes
+    #[clap(long, value_parser, default_value_t = 300)]
+    interval: u64,
+}
+
+fn exit_with_error(context: &str, error: &dyn std::error::Error) -> ! {
+    eprintln!("Error {context}: {}", error_chain(error));
+    process::exit(1);
+}
+
+fn open_interface(config: &Config) -> DatabaseInterface {
+    let db = storage::open(config).unwrap_or_else(|e| exit_with_error("opening database", &e));
+    DatabaseInterface::from(db, WebClient::new(config.timeout), config.rules.clone())
+}
+
+/// Accepts the configured username and password or those of a user in the database
+/// (X-Username/X-Password) or, if one is configured, the API token (Authorization: Bearer)
+fn check_credentials(
+    request: &Request,
+    server: &Server,
+    interface: &DatabaseInterface,
+) -> Result<(), ApiError> {
+    if let (Some(token), Some(header)) = (&server.token, request.header("Authorization")) {
+        return match header.strip_prefix("Bearer ") {
+            Some(given) if users::secrets_match(given, token) => Ok(()),
+            _ => Err(ApiError::new(ErrorCode::Unauthorized, "Token incorrect")),
+        };
+    }
+    match (request.header("X-Username"), request.header("X-Password")) {
+        (Some(username), Some(password))
+            if !server.username.is_empty()
+                && username == server.username
+                && users::secrets_match(password, &server.password) =>
+        {
+            Ok(())
+        }
+        (Some(username), Some(password)) => match interface.check_user(username, password) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(ApiError::new(
+                ErrorCode::Unauthorized,
+                "Username or password incorrect",
+            )),
+            Err(e) => Err(e.into()),
+        },
+        _ => Err(ApiError::new(
+            ErrorCode::Unauthorized,
+            "X-Username and X-Password headers required",
+        )),
+    }
+}
+
+fn serve(config: Config) {
+    let interface = Mutex::new(open_interface(&config));
+    let server = config.server.unwrap_or_else(|| {
+        exit_with_error("starting server", &config::Error::MissingSection("server"))
+    });
+    println!("Now listening on {}", server.url);
+
+    rouille::start_server(server.url.clone(), move |request| {
+        rouille::log(request, io::stdout(), || {
+            handle(request, &server, &interface)
         })
     });
 }
+
+/// Answers one request to the API
+fn handle(request: &Request, server: &Server, interface: &Mutex<DatabaseInterface>) -> Response {
+    if request.method() == "OPTIONS" {
+        return Response::empty_204()
+            .with_additional_header("Access-Control-Allow-Origin", "*")
+            .with_additional_header(
+                "Access-Control-Allow-Methods",
+                "GET, POST, PUT, PATCH, DELETE, OPTIONS",
+            )
+            .with_additional_header(
+                "Access-Control-Allow-Headers",
+                "content-type, x-username, x-password, authorization",
+            )
+            .with_additional_header("Access-Control-Max-Age", "86400");
+    }
+    // Bound first so the lock is released before the route takes it again
+    let auth = check_credentials(request, server, &interface.lock().unwrap());
+    if let Err(e) = auth {
+        return error_response(e);
+    }
+    router!(request,
+        (POST) (/bookmark) => {
+            add(&mut interface.lock().unwrap(), request)
+        },
+        (PUT) (/bookmark) => {
+            edit(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/bookmark/{id: i64}) => {
+            get(&mut interface.lock().unwrap(), request, id)
+        },
+        (DELETE) (/bookmark/{id: i64}) => {
+            delete_bookmark(&mut interface.lock().unwrap(), request, id)
+        },
+        (POST) (/bookmark/{id: i64}/visit) => {
+            visit(&mut interface.lock().unwrap(), request, id)
+        },
+        (POST) (/bookmark/{id: i64}/trash) => {
+            trash_bookmark(&mut interface.lock().unwrap(), request, id)
+        },
+        (POST) (/bookmark/{id: i64}/restore) => {
+            restore_bookmark(&mut interface.lock().unwrap(), request, id)
+        },
+        (GET) (/trash) => {
+            trash(&mut interface.lock().unwrap(), request)
+        },
+        (DELETE) (/trash) => {
+            empty_trash(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/search) => {
+            search(&mut interface.lock().unwrap(), request)
+        },
+        (DELETE) (/search) => {
+            delete(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/tags) => {
+            tags(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/tags/tree) => {
+            tag_tree(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/tags/aliases) => {
+            tag_aliases(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/tags/details) => {
+            tag_details(&mut interface.lock().unwrap(), request)
+        },
+        (POST) (/tags/aliases) => {
+            add_tag_alias(&mut interface.lock().unwrap(), request)
+        },
+        (PATCH) (/tags) => {
+            rename_tag(&mut interface.lock().unwrap(), request)
+        },
+        (DELETE) (/tags) => {
+            delete_tag(&mut interface.lock().unwrap(), request)
+        },
+        (POST) (/tags/merge) => {
+            merge_tags(&mut interface.lock().unwrap(), request)
+        },
+        (POST) (/tags/prune) => {
+            prune_tags(&mut interface.lock().unwrap(), request)
+        },
+        (POST) (/retag) => {
+            retag(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/suggest-tags) => {
+            suggest_tags(&mut interface.lock().unwrap(), request)
+        },
+        (POST) (/rules/apply) => {
+            apply_rules(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/queue) => {
+            queue(&mut interface.lock().unwrap(), request)
+        },
+        (POST) (/read) => {
+            read(&mut interface.lock().unwrap(), request)
+        },
+        (POST) (/unread) => {
+            unread(&mut interface.lock().unwrap(), request)
+        },
+        (GET) (/changes) => {
+            changes(&mut interface.lock().unwrap(), request)
+        },
+        (POST) (/sync) => {
+            sync(&mut interface.lock().unwrap(), request)
+        },
+        // /tags/<name> comes last since hierarchical names contain slashes (which
+        // also means tags named like the routes above can't be fetched this way)
+        _ => match (request.method(), tag_name(request)) {
+            ("GET", Some(name)) => {
+                tag_info(&mut interface.lock().unwrap(), request, &name)
+            }
+            ("PATCH", Some(name)) => {
+                edit_tag(&mut interface.lock().unwrap(), request, &name)
+            }
+            _ => error_response(ApiError::new(ErrorCode::NotFound, "No such route")),
+        }
+    )
+    .with_additional_header("Access-Control-Allow-Origin", "*")
+}
+
+fn mirror(config: Config, args: Mirror) -> ! {
+    let mut interface = open_interface(&config);
+    let token = args
+        .token
+        .or_else(|| env::var("SYL_TOKEN").ok())
+        .unwrap_or_else(|| {
+            eprintln!(
+                "Error: give the token for {} with SYL_TOKEN or --token",
+                args.from
+            );
+            process::exit(1);
+        });
+    let remote = ServerInterface::with_token(&args.from, &token);
+    println!("Mirroring {} every {} seconds", args.from, args.interval);
+    loop {
+        match interface.mirror(&args.from, args.tag.as_deref(), |since| {
+            remote.changes(since)
+        }) {
+            Ok(0) => {}
+            Ok(count) => println!(
+                "Mirrored {count} {} from {}",
+                singular_plural("bookmarks", count as isize),
+                args.from
+            ),
+            // Keep going; the server may just be down for a while
+            Err(e) => eprintln!("Error mirroring {}: {}", args.from, error_chain(&e)),
+        }
+        thread::sleep(Duration::from_secs(args.interval));
+    }
+}
+
+fn manage_users(config: Config, command: Users) {
+    let mut interface = open_interface(&config);
+    match command {
+        Users::Add { username } => {
+            let mut password = String::new();
+            io::stdin()
+                .read_line(&mut password)
+                .unwrap_or_else(|e| exit_with_error("reading the password", &e));
+            let password = password.trim_end_matches(['\r', '\n']);
+            match interface.add_user(&username, password) {
+                Ok(_) => println!("Added {username}"),
+                Err(e) => exit_with_error("adding the user", &e),
+            }
+        }
+        Users::List => {
+            let users = interface
+                .users()
+                .unwrap_or_else(|e| exit_with_error("listing the users", &e));
+            for user in users {
+                println!("{}", user.username);
+            }
+        }
+        Users::Remove { username } => match interface.remove_user(&username) {
+            Ok(true) => println!("Removed {username}"),
+            Ok(false) => {
+                eprintln!("No user named {username}");
+                process::exit(1);
+            }
+            Err(e) => exit_with_error("removing the user", &e),
+        },
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+    let config = Config::open(ConfigPath::ServerDefault)
+        .unwrap_or_else(|e| exit_with_error("reading config", &e));
+
+    match args.command {
+        Some(Command::Mirror(mirror_args)) => mirror(config, mirror_args),
+        Some(Command::Users(command)) => manage_users(config, command),
+        Some(Command::Serve) | None => serve(config),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use syl_lib::db::Database;
+    use syl_lib::sync::ConflictRule;
+
+    use super::*;
+
+    #[test]
+    fn test_authenticated_request() {
+        let server = Server {
+            url: "localhost:0".to_string(),
+            username: "me".to_string(),
+            password: "pw".to_string(),
+            token: None,
+            sync: false,
+            conflicts: ConflictRule::default(),
+        };
+        let interface = Mutex::new(DatabaseInterface::from(
+            Box::new(Database::open(":memory:").unwrap()),
+            WebClient::new(Duration::from_secs(1)),
+            Vec::new(),
+        ));
+        let request = |password: &str| {
+            Request::fake_http(
+                "GET",
+                "/tags",
+                vec![
+                    ("X-Username".to_string(), "me".to_string()),
+                    ("X-Password".to_string(), password.to_string()),
+                ],
+                Vec::new(),
+            )
+        };
+
+        // Run on another thread so a request that never finishes fails the test instead of
+        // hanging it
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for password in ["pw", "wrong"] {
+                let response = handle(&request(password), &server, &interface);
+                sender.send(response.status_code).unwrap();
+            }
+        });
+        let timeout = Duration::from_secs(10);
+        assert_eq!(receiver.recv_timeout(timeout), Ok(200));
+        assert_eq!(receiver.recv_timeout(timeout), Ok(401));
+    }
+}
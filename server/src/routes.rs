@@ -1,76 +1,234 @@
+use rouille::input::json::JsonError;
 use rouille::input::json_input;
-use rouille::{try_or_400, Request, Response};
-use serde::Serialize;
-use syl_lib::commands::{Add, DatabaseInterface, Delete, Edit, Interface, RenameTag, Search, Tags};
+use rouille::{Request, Response};
+use serde::de::DeserializeOwned;
+use syl_lib::api::{ApiError, ErrorCode};
+use syl_lib::commands::{
+    Add, ApplyRules, Changes, DatabaseInterface, Delete, DeleteTag, Edit, EditTag, Error,
+    Interface, MarkRead, MergeTags, RenameTag, Retag, Search, SuggestTags, TagAlias, Tags,
+};
+use syl_lib::db::AddOutcome;
+use syl_lib::sync::SyncRequest;
 use urlencoding::decode;
 
-#[derive(Serialize)]
-struct Error {
-    message: String,
+/// Like rouille's `try_or_400!`, but responds with the matching [ApiError] instead of a bare 400.
+macro_rules! try_or_error {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(e) => return error_response(ApiError::from(e)),
+        }
+    };
+}
+
+pub fn error_response(error: ApiError) -> Response {
+    let status = error.status();
+    Response::json(&error).with_status_code(status)
+}
+
+fn json_args<T: DeserializeOwned>(request: &Request) -> Result<T, ApiError> {
+    json_input(request).map_err(|e| match e {
+        JsonError::ParseError(e) if e.is_data() => {
+            ApiError::new(ErrorCode::InvalidQuery, format!("Invalid arguments: {e}"))
+        }
+        e => ApiError::new(ErrorCode::BadRequest, format!("Invalid JSON body: {e}")),
+    })
+}
+
+fn query_args<T: DeserializeOwned>(request: &Request) -> Result<T, ApiError> {
+    let query = decode(request.raw_query_string()).map_err(|_| {
+        ApiError::new(ErrorCode::BadRequest, "Query string is not valid UTF-8")
+    })?;
+    serde_qs::from_str(&query).map_err(|e| match e {
+        serde_qs::Error::Custom(message) => ApiError::new(
+            ErrorCode::InvalidQuery,
+            format!("Invalid arguments: {message}"),
+        ),
+        e => ApiError::new(ErrorCode::BadRequest, format!("Invalid query string: {e}")),
+    })
 }
 
 pub fn add(interface: &mut DatabaseInterface, request: &Request) -> Response {
-    let args: Add = try_or_400!(json_input(request));
-    match interface.add(args) {
-        Ok(bookmark) => Response::json(&bookmark),
-        Err(e) => Response::json(&Error {
-            message: format!("Error writing bookmark to database: {e:?}"),
-        }),
+    let args: Add = try_or_error!(json_args(request));
+    match try_or_error!(interface.add(args)) {
+        (bookmark, AddOutcome::Created) => Response::json(&bookmark).with_status_code(201),
+        (bookmark, AddOutcome::Merged) => Response::json(&bookmark),
+        (bookmark, AddOutcome::AlreadyExists) => {
+            error_response(ApiError::from(Error::DuplicateUrl(Box::new(bookmark))))
+        }
     }
 }
 
 pub fn edit(interface: &mut DatabaseInterface, request: &Request) -> Response {
-    let args: Edit = try_or_400!(json_input(request));
-    match interface.edit(args) {
-        Ok(bookmark) => Response::json(&bookmark),
-        Err(e) => Response::json(&Error {
-            message: format!("Error editing bookmark: {e:?}"),
-        }),
-    }
+    let args: Edit = try_or_error!(json_args(request));
+    let bookmark = try_or_error!(interface.edit(args));
+    Response::json(&bookmark)
+}
+
+pub fn get(interface: &mut DatabaseInterface, _request: &Request, id: i64) -> Response {
+    let bookmark = try_or_error!(interface.get(id));
+    Response::json(&bookmark)
+}
+
+pub fn delete_bookmark(interface: &mut DatabaseInterface, _request: &Request, id: i64) -> Response {
+    let bookmark = try_or_error!(interface.delete_bookmark(id));
+    Response::json(&bookmark)
+}
+
+pub fn trash_bookmark(interface: &mut DatabaseInterface, _request: &Request, id: i64) -> Response {
+    let bookmark = try_or_error!(interface.trash_bookmark(id));
+    Response::json(&bookmark)
+}
+
+pub fn restore_bookmark(
+    interface: &mut DatabaseInterface,
+    _request: &Request,
+    id: i64,
+) -> Response {
+    let bookmark = try_or_error!(interface.restore_bookmark(id));
+    Response::json(&bookmark)
+}
+
+pub fn trash(interface: &mut DatabaseInterface, _request: &Request) -> Response {
+    let bookmarks = try_or_error!(interface.trash());
+    Response::json(&bookmarks)
+}
+
+pub fn empty_trash(interface: &mut DatabaseInterface, _request: &Request) -> Response {
+    let deleted = try_or_error!(interface.empty_trash());
+    Response::json(&deleted)
+}
+
+pub fn visit(interface: &mut DatabaseInterface, _request: &Request, id: i64) -> Response {
+    let bookmark = try_or_error!(interface.visit(id));
+    Response::json(&bookmark)
 }
 
 pub fn search(interface: &mut DatabaseInterface, request: &Request) -> Response {
-    let args: Search =
-        serde_qs::from_str(&decode(request.raw_query_string()).expect("invalid UTF-8!")).unwrap();
-    match interface.find(args) {
-        Ok(bookmarks) => Response::json(&bookmarks),
-        Err(e) => Response::json(&Error {
-            message: format!("Error searching bookmarks: {e:?}"),
-        }),
-    }
+    let args: Search = try_or_error!(query_args(request));
+    let bookmarks = try_or_error!(interface.find(args));
+    Response::json(&bookmarks)
 }
 
 pub fn delete(interface: &mut DatabaseInterface, request: &Request) -> Response {
-    let args: Delete =
-        serde_qs::from_str(&decode(request.raw_query_string()).expect("invalid UTF-8!")).unwrap();
-    match interface.delete(args) {
-        Ok(deleted) => Response::json(&deleted),
-        Err(e) => Response::json(&Error {
-            message: format!("Error deleting bookmarks: {e:?}"),
-        }),
-    }
+    let args: Delete = try_or_error!(query_args(request));
+    let deleted = try_or_error!(interface.delete(args));
+    Response::json(&deleted)
 }
 
 pub fn tags(interface: &mut DatabaseInterface, request: &Request) -> Response {
-    let args: Tags =
-        serde_qs::from_str(&decode(request.raw_query_string()).expect("invalid UTF-8!"))
-            .expect("Invalid arguments");
-    match interface.tags(args) {
-        Ok(tags) => Response::json(&tags),
-        Err(e) => Response::json(&Error {
-            message: format!("Error searching bookmarks: {e:?}"),
-        }),
-    }
+    let args: Tags = try_or_error!(query_args(request));
+    let tags = try_or_error!(interface.tags(args));
+    Response::json(&tags)
+}
+
+pub fn tag_tree(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: Tags = try_or_error!(query_args(request));
+    let tree = try_or_error!(interface.tag_tree(args));
+    Response::json(&tree)
 }
 
 pub fn rename_tag(interface: &mut DatabaseInterface, request: &Request) -> Response {
-    let args: RenameTag =
-        serde_qs::from_str(&decode(request.raw_query_string()).expect("invalid UTF-8!"))
-            .expect("Invalid arguments");
-    match interface.rename_tag(args) {
-        Ok(count) => Response::json(&count),
-        Err(e) => Response::json(&Error {
-            message: format!("Error renaming bookmarks: {e:?}"),
-        }),
-    }
+    let args: RenameTag = try_or_error!(query_args(request));
+    let count = try_or_error!(interface.rename_tag(args));
+    Response::json(&count)
+}
+
+pub fn tag_aliases(interface: &mut DatabaseInterface, _request: &Request) -> Response {
+    let aliases = try_or_error!(interface.tag_aliases());
+    Response::json(&aliases)
+}
+
+pub fn add_tag_alias(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: TagAlias = try_or_error!(json_args(request));
+    let count = try_or_error!(interface.add_tag_alias(args));
+    Response::json(&count)
+}
+
+pub fn tag_details(interface: &mut DatabaseInterface, _request: &Request) -> Response {
+    let infos = try_or_error!(interface.tag_infos());
+    Response::json(&infos)
+}
+
+/// The tag named by the rest of a `/tags/<name>` path, which may contain slashes
+pub fn tag_name(request: &Request) -> Option<String> {
+    let path = request.raw_url().split('?').next().unwrap_or_default();
+    let name = path.strip_prefix("/tags/")?;
+    decode(name).ok().map(|name| name.into_owned())
+}
+
+pub fn tag_info(interface: &mut DatabaseInterface, _request: &Request, name: &str) -> Response {
+    let info = try_or_error!(interface.tag_info(name));
+    Response::json(&info)
+}
+
+pub fn edit_tag(interface: &mut DatabaseInterface, request: &Request, name: &str) -> Response {
+    let mut args: EditTag = try_or_error!(json_args(request));
+    args.name = name.to_string();
+    let info = try_or_error!(interface.edit_tag(args));
+    Response::json(&info)
+}
+
+pub fn delete_tag(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: DeleteTag = try_or_error!(query_args(request));
+    let count = try_or_error!(interface.delete_tag(args));
+    Response::json(&count)
+}
+
+pub fn merge_tags(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: MergeTags = try_or_error!(json_args(request));
+    let count = try_or_error!(interface.merge_tags(args));
+    Response::json(&count)
+}
+
+pub fn prune_tags(interface: &mut DatabaseInterface, _request: &Request) -> Response {
+    let count = try_or_error!(interface.prune_tags());
+    Response::json(&count)
+}
+
+pub fn retag(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: Retag = try_or_error!(json_args(request));
+    let count = try_or_error!(interface.retag(args));
+    Response::json(&count)
+}
+
+pub fn suggest_tags(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: SuggestTags = try_or_error!(query_args(request));
+    let suggestions = try_or_error!(interface.suggest_tags(args));
+    Response::json(&suggestions)
+}
+
+pub fn apply_rules(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: ApplyRules = try_or_error!(json_args(request));
+    let matches = try_or_error!(interface.apply_rules(args));
+    Response::json(&matches)
+}
+
+pub fn queue(interface: &mut DatabaseInterface, _request: &Request) -> Response {
+    let bookmarks = try_or_error!(interface.queue());
+    Response::json(&bookmarks)
+}
+
+pub fn read(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: MarkRead = try_or_error!(json_args(request));
+    let bookmark = try_or_error!(interface.read(args));
+    Response::json(&bookmark)
+}
+
+pub fn unread(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: MarkRead = try_or_error!(json_args(request));
+    let bookmark = try_or_error!(interface.unread(args));
+    Response::json(&bookmark)
+}
+
+pub fn changes(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: Changes = try_or_error!(query_args(request));
+    let feed = try_or_error!(interface.changes(args));
+    Response::json(&feed)
+}
+
+pub fn sync(interface: &mut DatabaseInterface, request: &Request) -> Response {
+    let args: SyncRequest = try_or_error!(json_args(request));
+    let response = try_or_error!(interface.handle_sync(args));
+    Response::json(&response)
 }
@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{commands::Error, db::Bookmark, util::error_chain};
+
+/// Stable, machine-readable identifiers for the errors the server can return. Clients should match
+/// on these rather than on the message, which is meant for humans and may change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The request couldn't be read at all (invalid UTF-8, wrong content type, malformed JSON...)
+    BadRequest,
+    /// Missing or incorrect credentials
+    Unauthorized,
+    /// The requested bookmark, tag or route doesn't exist
+    NotFound,
+    /// A bookmark for the given URL already exists
+    DuplicateUrl,
+    /// The bookmark is mirrored from another server and can't be changed
+    ReadOnly,
+    /// The request was well-formed, but its arguments didn't make sense
+    InvalidQuery,
+    /// Something went wrong on the server's end
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(&self) -> u16 {
+        match self {
+            ErrorCode::BadRequest => 400,
+            ErrorCode::Unauthorized => 401,
+            ErrorCode::ReadOnly => 403,
+            ErrorCode::NotFound => 404,
+            ErrorCode::DuplicateUrl => 409,
+            ErrorCode::InvalidQuery => 422,
+            ErrorCode::Internal => 500,
+        }
+    }
+}
+
+/// The JSON body of every error response from the server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// Extra, code-specific information, e.g. the ID that wasn't found or the bookmark that
+    /// already exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<Value>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn status(&self) -> u16 {
+        self.code.status()
+    }
+}
+
+impl From<&Error> for ApiError {
+    fn from(error: &Error) -> Self {
+        match error {
+            Error::NotFound(id) => ApiError::new(
+                ErrorCode::NotFound,
+                format!("No bookmark with ID {id} exists"),
+            )
+            .with_details(json!({ "id": id })),
+            Error::TagNotFound(name) => {
+                ApiError::new(ErrorCode::NotFound, format!("No tag named {name} exists"))
+                    .with_details(json!({ "tag": name }))
+            }
+            Error::UrlNotFound(url) => {
+                ApiError::new(ErrorCode::NotFound, format!("No bookmark for {url} exists"))
+                    .with_details(json!({ "url": url }))
+            }
+            Error::DuplicateUrl(bookmark) => ApiError::new(
+                ErrorCode::DuplicateUrl,
+                format!("A bookmark for {} already exists", bookmark.url),
+            )
+            .with_details(json!({ "bookmark": bookmark })),
+            Error::ReadOnly { id, origin } => ApiError::new(
+                ErrorCode::ReadOnly,
+                format!("Bookmark {id} is mirrored from {origin} and can't be changed"),
+            )
+            .with_details(json!({ "id": id, "origin": origin })),
+            Error::InvalidQuery(message) => {
+                ApiError::new(ErrorCode::InvalidQuery, message.to_string())
+            }
+            Error::Unauthorized => {
+                ApiError::new(ErrorCode::Unauthorized, "Username or password incorrect")
+            }
+            Error::Server(api_error) => ApiError {
+                code: api_error.code,
+                message: api_error.message.to_string(),
+                details: api_error.details.clone(),
+            },
+            e => ApiError::new(ErrorCode::Internal, error_chain(e)),
+        }
+    }
+}
+
+impl From<Error> for ApiError {
+    fn from(error: Error) -> Self {
+        ApiError::from(&error)
+    }
+}
+
+/// Turns an error response from the server back into the [Error] variant it was created from,
+/// falling back to [Error::Server] when the details are missing or unrecognized.
+impl From<ApiError> for Error {
+    fn from(error: ApiError) -> Self {
+        let details = error.details.as_ref();
+        match error.code {
+            ErrorCode::NotFound => {
+                let id = details.and_then(|d| d.get("id")).and_then(Value::as_i64);
+                let tag = details.and_then(|d| d.get("tag")).and_then(Value::as_str);
+                let url = details.and_then(|d| d.get("url")).and_then(Value::as_str);
+                match (id, tag, url) {
+                    (Some(id), _, _) => Error::NotFound(id),
+                    (None, Some(tag), _) => Error::TagNotFound(tag.to_string()),
+                    (None, None, Some(url)) => Error::UrlNotFound(url.to_string()),
+                    _ => Error::Server(error),
+                }
+            }
+            ErrorCode::DuplicateUrl => {
+                match details
+                    .and_then(|d| d.get("bookmark"))
+                    .and_then(|b| serde_json::from_value::<Bookmark>(b.clone()).ok())
+                {
+                    Some(bookmark) => Error::DuplicateUrl(Box::new(bookmark)),
+                    None => Error::Server(error),
+                }
+            }
+            ErrorCode::ReadOnly => {
+                let id = details.and_then(|d| d.get("id")).and_then(Value::as_i64);
+                let origin = details
+                    .and_then(|d| d.get("origin"))
+                    .and_then(Value::as_str);
+                match (id, origin) {
+                    (Some(id), Some(origin)) => Error::ReadOnly {
+                        id,
+                        origin: origin.to_string(),
+                    },
+                    _ => Error::Server(error),
+                }
+            }
+            ErrorCode::InvalidQuery | ErrorCode::BadRequest => Error::InvalidQuery(error.message),
+            ErrorCode::Unauthorized => Error::Unauthorized,
+            _ => Error::Server(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_round_trip() {
+        let error: Error = ApiError::from(Error::NotFound(7)).into();
+        assert!(matches!(error, Error::NotFound(7)));
+        let error: Error = ApiError::from(Error::TagNotFound("rust".to_string())).into();
+        assert!(matches!(error, Error::TagNotFound(tag) if tag == "rust"));
+        let error: Error = ApiError::from(Error::UrlNotFound("example.org".to_string())).into();
+        assert!(matches!(error, Error::UrlNotFound(url) if url == "example.org"));
+
+        let bookmark = Bookmark {
+            id: 3,
+            url: "example.org".to_string(),
+            title: None,
+            description: None,
+            tags: vec!["tag".to_string()],
+            origin: None,
+            unread: false,
+            read_at: None,
+            notes: None,
+            highlights: Vec::new(),
+            created_at: None,
+            last_visited: None,
+            visit_count: 0,
+            deleted_at: None,
+        };
+        let api_error = ApiError::from(Error::DuplicateUrl(Box::new(bookmark)));
+        assert_eq!(api_error.status(), 409);
+        let json = serde_json::to_string(&api_error).unwrap();
+        let error: Error = serde_json::from_str::<ApiError>(&json).unwrap().into();
+        match error {
+            Error::DuplicateUrl(bookmark) => assert_eq!(bookmark.id, 3),
+            e => panic!("Expected DuplicateUrl, got {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_unknown_details() {
+        let error: Error = ApiError::new(ErrorCode::NotFound, "nope").into();
+        assert!(matches!(error, Error::Server(_)));
+    }
+}
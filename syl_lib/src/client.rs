@@ -0,0 +1,326 @@
+//! The HTTP client side of the server's API: an [Interface] that sends each command to a syl
+//! server
+
+use crate::{
+    api::{ApiError, ErrorCode},
+    commands::{
+        Add, ApplyRules, Delete, DeleteTag, Edit, EditTag, Error as CommandError, Interface,
+        MarkRead, MergeTags, RenameTag, Result, Retag, Search, SuggestTags, TagAlias, Tags,
+    },
+    config::Server,
+    db::{AddOutcome, Bookmark},
+    rules::RuleMatch,
+    suggest::TagSuggestion,
+    sync::{ChangeFeed, SyncRequest, SyncResponse},
+    tags::{TagInfo, TagNode},
+};
+
+pub struct ServerInterface {
+    url: String,
+    username: String,
+    password: String,
+    token: Option<String>,
+}
+
+impl ServerInterface {
+    pub fn new(server: Server) -> Self {
+        Self {
+            url: server.url,
+            username: server.username,
+            password: server.password,
+            token: server.token,
+        }
+    }
+
+    /// Connects with an API token rather than a username and password
+    pub fn with_token(url: &str, token: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            username: String::new(),
+            password: String::new(),
+            token: Some(token.to_string()),
+        }
+    }
+
+    fn send(&self, verb: &str, path: &str, body: Option<&str>) -> Result<ureq::Response> {
+        let mut request = ureq::request(verb, &(self.url.to_string() + path));
+        request = match &self.token {
+            Some(token) => request.set("Authorization", &format!("Bearer {token}")),
+            None => request
+                .set("X-Username", &self.username)
+                .set("X-Password", &self.password),
+        };
+        let result;
+        if let Some(body) = body {
+            request = request.set("Content-Type", "application/json");
+            result = request.send_string(body);
+        } else {
+            result = request.call();
+        }
+
+        result.map_err(|e| match e {
+            // Error responses from the server carry an ApiError body describing what went wrong;
+            // anything else (e.g. a proxy's HTML error page) is passed along as-is
+            ureq::Error::Status(code, response) => {
+                let body = response.into_string().unwrap_or_default();
+                match serde_json::from_str::<ApiError>(&body) {
+                    Ok(api_error) => CommandError::from(api_error),
+                    Err(_) => CommandError::Server(ApiError::new(
+                        ErrorCode::Internal,
+                        format!("HTTP {code}: {body}"),
+                    )),
+                }
+            }
+            ureq::Error::Transport(transport) => CommandError::Network(Box::new(transport)),
+        })
+    }
+
+    fn request(&self, verb: &str, path: &str, body: Option<&str>) -> Result<String> {
+        self.send(verb, path, body)?
+            .into_string()
+            .map_err(CommandError::Io)
+    }
+
+    /// The path to a tag's details; its segments are encoded separately so the slashes in
+    /// hierarchical tags stay readable
+    fn tag_path(name: &str) -> String {
+        let segments: Vec<_> = name.split('/').map(urlencoding::encode).collect();
+        format!("/tags/{}", segments.join("/"))
+    }
+
+    /// Reads a page of the server's change log
+    pub fn changes(&self, since: i64) -> Result<ChangeFeed> {
+        serde_json::from_str(&self.request("GET", &format!("/changes?since={since}"), None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    /// Sends a local replica's changes to the server and returns the server's
+    pub fn sync(&self, request: &SyncRequest) -> Result<SyncResponse> {
+        serde_json::from_str(&self.request(
+            "POST",
+            "/sync",
+            Some(&serde_json::to_string(request).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+}
+
+impl Interface for ServerInterface {
+    fn add(&mut self, args: Add) -> Result<(Bookmark, AddOutcome)> {
+        let response = self.send(
+            "POST",
+            "/bookmark",
+            Some(&serde_json::to_string(&args).unwrap()),
+        );
+        match response {
+            Ok(response) => {
+                let outcome = if response.status() == 201 {
+                    AddOutcome::Created
+                } else {
+                    AddOutcome::Merged
+                };
+                let body = response.into_string().map_err(CommandError::Io)?;
+                let bookmark =
+                    serde_json::from_str(&body).map_err(CommandError::InvalidResponse)?;
+                Ok((bookmark, outcome))
+            }
+            Err(CommandError::DuplicateUrl(bookmark)) => Ok((*bookmark, AddOutcome::AlreadyExists)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn find(&self, args: Search) -> Result<Vec<Bookmark>> {
+        serde_json::from_str(&self.request(
+            "GET",
+            &("/search?".to_string() + &serde_qs::to_string(&args).unwrap()),
+            None,
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn tags(&self, args: Tags) -> Result<Vec<(String, usize)>> {
+        serde_json::from_str(&self.request(
+            "GET",
+            &("/tags?".to_string() + &serde_qs::to_string(&args).unwrap()),
+            None,
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn tag_tree(&self, args: Tags) -> Result<Vec<TagNode>> {
+        serde_json::from_str(&self.request(
+            "GET",
+            &format!("/tags/tree?{}", &serde_qs::to_string(&args).unwrap()),
+            None,
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn rename_tag(&self, args: RenameTag) -> Result<usize> {
+        serde_json::from_str(&self.request(
+            "PATCH",
+            &format!("/tags?{}", &serde_qs::to_string(&args).unwrap()),
+            None,
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn add_tag_alias(&self, args: TagAlias) -> Result<usize> {
+        serde_json::from_str(&self.request(
+            "POST",
+            "/tags/aliases",
+            Some(&serde_json::to_string(&args).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn tag_aliases(&self) -> Result<Vec<(String, String)>> {
+        serde_json::from_str(&self.request("GET", "/tags/aliases", None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn tag_info(&self, name: &str) -> Result<TagInfo> {
+        serde_json::from_str(&self.request("GET", &Self::tag_path(name), None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn tag_infos(&self) -> Result<Vec<TagInfo>> {
+        serde_json::from_str(&self.request("GET", "/tags/details", None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn edit_tag(&self, args: EditTag) -> Result<TagInfo> {
+        serde_json::from_str(&self.request(
+            "PATCH",
+            &Self::tag_path(&args.name),
+            Some(&serde_json::to_string(&args).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn delete_tag(&self, args: DeleteTag) -> Result<usize> {
+        serde_json::from_str(&self.request(
+            "DELETE",
+            &format!("/tags?{}", &serde_qs::to_string(&args).unwrap()),
+            None,
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn merge_tags(&self, args: MergeTags) -> Result<usize> {
+        serde_json::from_str(&self.request(
+            "POST",
+            "/tags/merge",
+            Some(&serde_json::to_string(&args).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn prune_tags(&self) -> Result<usize> {
+        serde_json::from_str(&self.request("POST", "/tags/prune", None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn retag(&mut self, args: Retag) -> Result<usize> {
+        serde_json::from_str(&self.request(
+            "POST",
+            "/retag",
+            Some(&serde_json::to_string(&args).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn suggest_tags(&self, args: SuggestTags) -> Result<Vec<TagSuggestion>> {
+        serde_json::from_str(&self.request(
+            "GET",
+            &format!("/suggest-tags?{}", &serde_qs::to_string(&args).unwrap()),
+            None,
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn apply_rules(&mut self, args: ApplyRules) -> Result<Vec<RuleMatch>> {
+        serde_json::from_str(&self.request(
+            "POST",
+            "/rules/apply",
+            Some(&serde_json::to_string(&args).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn queue(&self) -> Result<Vec<Bookmark>> {
+        serde_json::from_str(&self.request("GET", "/queue", None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn read(&mut self, args: MarkRead) -> Result<Bookmark> {
+        serde_json::from_str(&self.request(
+            "POST",
+            "/read",
+            Some(&serde_json::to_string(&args).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn unread(&mut self, args: MarkRead) -> Result<Bookmark> {
+        serde_json::from_str(&self.request(
+            "POST",
+            "/unread",
+            Some(&serde_json::to_string(&args).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn edit(&mut self, args: Edit) -> Result<Bookmark> {
+        serde_json::from_str(&self.request(
+            "PUT",
+            "/bookmark",
+            Some(&serde_json::to_string(&args).unwrap()),
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn get(&self, id: i64) -> Result<Bookmark> {
+        serde_json::from_str(&self.request("GET", &format!("/bookmark/{id}"), None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn visit(&mut self, id: i64) -> Result<Bookmark> {
+        serde_json::from_str(&self.request("POST", &format!("/bookmark/{id}/visit"), None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn delete(&self, args: Delete) -> Result<Vec<Bookmark>> {
+        serde_json::from_str(&self.request(
+            "DELETE",
+            &("/search?".to_string() + &serde_qs::to_string(&args).unwrap()),
+            None,
+        )?)
+        .map_err(CommandError::InvalidResponse)
+    }
+
+    fn delete_bookmark(&self, id: i64) -> Result<Bookmark> {
+        serde_json::from_str(&self.request("DELETE", &format!("/bookmark/{id}"), None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn trash_bookmark(&mut self, id: i64) -> Result<Bookmark> {
+        serde_json::from_str(&self.request("POST", &format!("/bookmark/{id}/trash"), None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn restore_bookmark(&mut self, id: i64) -> Result<Bookmark> {
+        serde_json::from_str(&self.request("POST", &format!("/bookmark/{id}/restore"), None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn trash(&self) -> Result<Vec<Bookmark>> {
+        serde_json::from_str(&self.request("GET", "/trash", None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+
+    fn empty_trash(&self) -> Result<Vec<Bookmark>> {
+        serde_json::from_str(&self.request("DELETE", "/trash", None)?)
+            .map_err(CommandError::InvalidResponse)
+    }
+}
@@ -1,21 +1,77 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
+    Red,
     Green,
     Yellow,
     Blue,
+    Magenta,
     Cyan,
+    White,
     BoldGreen,
 }
 
+/// The colors a tag can be given, by name
+pub const TAG_COLORS: &[(&str, Color)] = &[
+    ("red", Color::Red),
+    ("green", Color::Green),
+    ("yellow", Color::Yellow),
+    ("blue", Color::Blue),
+    ("magenta", Color::Magenta),
+    ("cyan", Color::Cyan),
+    ("white", Color::White),
+];
+
+impl Color {
+    /// Looks up a tag color by name (see [TAG_COLORS])
+    pub fn from_name(name: &str) -> Option<Color> {
+        TAG_COLORS
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, color)| *color)
+    }
+}
+
 pub fn color(string: &str, color: Color) -> String {
     format!(
         "\x1b[{}m{}\x1b[m",
         match color {
+            Color::Red => "31",
             Color::Green => "32",
             Color::Yellow => "33",
             Color::Blue => "34",
+            Color::Magenta => "35",
             Color::Cyan => "36",
+            Color::White => "37",
             Color::BoldGreen => "1;32",
         },
         string
     )
 }
+
+static TAG_COLOR_MAP: OnceLock<HashMap<String, Color>> = OnceLock::new();
+
+/// Sets the colors [tag] uses for the rest of the program. Only the first call has any effect.
+pub fn set_tag_colors(colors: HashMap<String, Color>) {
+    let _ = TAG_COLOR_MAP.set(colors);
+}
+
+/// The color of a tag: its own, or its nearest ancestor's, or yellow if neither has one
+pub fn tag_color(name: &str) -> Color {
+    let colors = TAG_COLOR_MAP.get();
+    let mut path = Some(name);
+    while let Some(p) = path {
+        if let Some(c) = colors.and_then(|colors| colors.get(p)) {
+            return *c;
+        }
+        path = p.rsplit_once('/').map(|(parent, _)| parent);
+    }
+    Color::Yellow
+}
+
+/// Colors a tag with [tag_color]
+pub fn tag(name: &str) -> String {
+    color(name, tag_color(name))
+}
@@ -1,11 +1,21 @@
-use std::{io, result, usize};
+use std::{error, fmt, io, result};
 
 use clap::Args;
+use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 
 use crate::{
-    db::{Bookmark, Database, Error as DatabaseError},
+    api::ApiError,
+    colors::{Color, TAG_COLORS},
+    db::{AddOutcome, Bookmark, Error as DatabaseError, User},
+    query,
+    rules::{self, Rule, RuleMatch},
+    storage::Storage,
+    suggest::{self, TagSuggestion},
+    sync::{self, ChangeFeed, ConflictRule, SyncReport, SyncRequest, SyncResponse},
+    tags::{self, TagInfo, TagNode},
+    users,
     web::{Metadata, WebClient},
 };
 
@@ -24,15 +34,56 @@ pub struct Add {
     /// Description for this bookmark; automatically fetched if not provided
     #[clap(short = 'd', long, value_parser)]
     pub description: Option<String>,
+    /// If a bookmark for this URL already exists, add the given tags to it
+    #[clap(short, long, action)]
+    #[serde(default)]
+    pub merge: bool,
+    /// Put the bookmark in the read-later queue (even if it already exists)
+    #[clap(short = 'l', long = "later", action)]
+    #[serde(default)]
+    pub unread: bool,
+    /// Suggest tags from the page's keywords, the site's other bookmarks and similar bookmarks,
+    /// and ask which to add
+    #[clap(long, action)]
+    #[serde(skip)]
+    pub suggest: bool,
 }
 
-#[serde_as]
+/// Picks a bookmark to mark read or unread, by ID or by URL
+#[derive(Args, Serialize, Deserialize)]
+pub struct MarkRead {
+    /// ID of the bookmark
+    #[clap(required_unless_present = "url")]
+    pub id: Option<i64>,
+    /// URL of the bookmark, instead of its ID
+    #[clap(long, value_parser, conflicts_with = "id")]
+    pub url: Option<String>,
+}
+
+/// Adds the tags from the config's rules to existing bookmarks
 #[derive(Args, Serialize, Deserialize)]
+pub struct ApplyRules {
+    /// Only show which bookmarks would get which tags
+    #[clap(long, action)]
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Arguments for suggesting tags for a page; see [crate::suggest]
+#[derive(Serialize, Deserialize)]
+pub struct SuggestTags {
+    pub url: String,
+}
+
+#[serde_as]
+#[derive(Args, Clone, Default, Serialize, Deserialize)]
 pub struct Search {
-    /// A word or phrase to match in the URL, title, or description
+    /// A word or phrase to match in the URL, title, description, notes or highlights; `tag:<tag>`
+    /// in the query works like -t, and `is:unread` or `is:read` filter by the read-later queue
     #[clap(value_parser)]
     pub query: Option<String>,
-    /// Limit search to tag(s); use this option multiple times to specify multiple tags
+    /// Limit search to tag(s), including the tags below them (-t lang matches lang/rust); use
+    /// this option multiple times to specify multiple tags
     #[clap(short, long = "tag", value_parser)]
     #[serde(default)]
     pub tags: Vec<String>,
@@ -54,125 +105,735 @@ pub struct Edit {
     #[clap(short = 'T', long = "remove-tag", value_parser)]
     #[serde(default)]
     pub remove_tags: Vec<String>,
+    /// Your own notes on the bookmark, in Markdown; an empty string removes them
+    #[clap(short, long, value_parser)]
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// A passage quoted from the page to keep with the bookmark
+    #[clap(long, value_parser)]
+    #[serde(default)]
+    pub highlight: Option<String>,
+    /// A comment on the highlight
+    #[clap(long, value_parser, requires = "highlight")]
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// ID(s) of highlights to remove, as shown by `syl show`
+    #[clap(long = "remove-highlight", value_parser)]
+    #[serde(default)]
+    pub remove_highlights: Vec<i64>,
 }
 
 #[derive(Args, Serialize, Deserialize)]
 pub struct Tags {
+    /// Sort tags by how many bookmarks use them rather than by name
     #[clap(short = 'c', long, action)]
     pub sort_by_count: bool,
+    /// Reverse the order
     #[clap(short, long, action)]
     pub reverse: bool,
+    /// Show tags as a tree, counting each bookmark under every tag above its own
+    #[clap(long, action)]
+    #[serde(default)]
+    pub tree: bool,
+    /// List groups of tags that look like duplicates (differing in case or by a typo)
+    #[clap(long, action)]
+    #[serde(default)]
+    pub suggest_merges: bool,
 }
 
+/// Makes one tag another name for another; tags given when adding, editing or searching are
+/// resolved through their aliases
+#[derive(Args, Serialize, Deserialize)]
+pub struct TagAlias {
+    /// The alternative name, e.g. js
+    pub alias: String,
+    /// The tag it stands for, e.g. javascript
+    pub tag: String,
+}
+
+/// Renames a tag along with every tag below it
 #[derive(Args, Serialize, Deserialize)]
 pub struct RenameTag {
+    /// The tag to rename, e.g. lang/js
     pub from: String,
+    /// Its new name, e.g. lang/javascript
     pub to: String,
 }
 
-// TODO: Figure out if there's a better way to keep this in sync with the Search API
+/// Sets a tag's description, color or pinned flag
+#[derive(Args, Serialize, Deserialize)]
+pub struct EditTag {
+    /// The tag to edit
+    #[serde(default)]
+    pub name: String,
+    /// What the tag is for; an empty string removes the description
+    #[clap(short, long, value_parser)]
+    pub description: Option<String>,
+    /// Color to show the tag in: red, green, yellow, blue, magenta, cyan or white; an empty
+    /// string goes back to the default
+    #[clap(short, long, value_parser)]
+    pub color: Option<String>,
+    /// Whether to list the tag before the others
+    #[clap(short, long, value_parser)]
+    pub pinned: Option<bool>,
+}
+
+/// Removes a tag (and every tag below it) from every bookmark
+#[derive(Args, Serialize, Deserialize)]
+pub struct DeleteTag {
+    /// The tag to remove
+    pub tag: String,
+}
+
+/// Renames several tags to one
+#[derive(Args, Serialize, Deserialize)]
+pub struct MergeTags {
+    /// The tags to merge
+    #[clap(required = true)]
+    pub tags: Vec<String>,
+    /// The tag to merge them into
+    #[clap(long, value_parser)]
+    pub into: String,
+}
+
+/// Adds and removes tags on every bookmark matching a search, all at once
 #[derive(Args, Serialize, Deserialize)]
+pub struct Retag {
+    #[serde(flatten)]
+    #[clap(flatten)]
+    pub search: Search,
+    /// Tag(s) to add
+    #[clap(long = "add", value_parser)]
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    /// Tag(s) to remove
+    #[clap(long = "remove", value_parser)]
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+}
+
+/// Deletes the bookmarks matching a search, or the bookmarks with the given IDs
+// TODO: Figure out if there's a better way to keep this in sync with the Search API
+#[derive(Args, Clone, Default, Serialize, Deserialize)]
 pub struct Delete {
     #[serde(flatten)]
     #[clap(flatten)]
     pub search: Search,
+    /// ID(s) of bookmarks to delete instead of searching, e.g. --id 3 --id 7 or --ids 3 7
+    #[clap(
+        long = "id",
+        visible_alias = "ids",
+        value_parser,
+        multiple_values = true,
+        conflicts_with_all = &["query", "tags"]
+    )]
+    #[serde(default)]
+    pub ids: Vec<i64>,
     /// Force deletion (i.e. don't show a confirmation prompt)
-    #[clap(short, action)]
+    #[clap(short, long, action)]
+    #[serde(default)]
     pub force: bool,
+    /// Only show which bookmarks would be deleted
+    #[clap(long, action)]
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Allow a search matching every bookmark (no query or tags) to delete them all
+    #[clap(long, action)]
+    #[serde(default)]
+    pub all: bool,
+}
+
+/// Arguments for reading the change log; see [sync::feed]
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct Changes {
+    /// Only return changes after this position in the change log
+    #[serde_as(as = "DisplayFromStr")]
+    #[serde(default)]
+    pub since: i64,
+    /// The most changes to return
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub limit: Option<usize>,
 }
 
 #[derive(Debug)]
 pub enum Error {
-    RusqliteError(rusqlite::Error),
-    UreqError(ureq::Error),
-    SerdeError,
-    IOError(io::Error),
+    /// No bookmark exists with the given ID
+    NotFound(i64),
+    /// No tag exists with the given name
+    TagNotFound(String),
+    /// No bookmark exists for the given URL
+    UrlNotFound(String),
+    /// A bookmark for the URL being added already exists
+    DuplicateUrl(Box<Bookmark>),
+    /// The bookmark is mirrored from another server, so it can't be changed here
+    ReadOnly {
+        id: i64,
+        origin: String,
+    },
+    /// The arguments given don't make up a valid query or command
+    InvalidQuery(String),
+    /// The server couldn't be reached
+    Network(Box<ureq::Transport>),
+    /// The server rejected the configured credentials
+    Unauthorized,
+    /// An error response from the server that doesn't map onto any of the other variants
+    Server(ApiError),
+    /// The server sent a response that couldn't be understood
+    InvalidResponse(serde_json::Error),
+    /// Reading from or writing to the database failed
+    Storage(DatabaseError),
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(id) => write!(f, "no bookmark with ID {id} exists"),
+            Error::TagNotFound(name) => write!(f, "no tag named {name} exists"),
+            Error::UrlNotFound(url) => write!(f, "no bookmark for {url} exists"),
+            Error::DuplicateUrl(bookmark) => write!(
+                f,
+                "a bookmark for {} already exists (ID {})",
+                bookmark.url, bookmark.id
+            ),
+            Error::ReadOnly { id, origin } => write!(
+                f,
+                "bookmark {id} is mirrored from {origin} and can't be changed here"
+            ),
+            Error::InvalidQuery(message) => write!(f, "invalid query: {message}"),
+            Error::Network(_) => write!(f, "couldn't reach the server"),
+            Error::Unauthorized => write!(f, "the server rejected the configured credentials"),
+            Error::Server(e) => write!(f, "the server returned an error: {}", e.message),
+            Error::InvalidResponse(_) => write!(f, "the server sent an unexpected response"),
+            Error::Storage(_) => write!(f, "database error"),
+            Error::Io(_) => write!(f, "I/O error"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Network(e) => Some(e.as_ref()),
+            Error::InvalidResponse(e) => Some(e),
+            Error::Storage(e) => Some(e),
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 pub type Result<T, E = Error> = result::Result<T, E>;
 
 pub trait Interface {
-    fn add(&mut self, args: Add) -> Result<Bookmark>;
+    fn add(&mut self, args: Add) -> Result<(Bookmark, AddOutcome)>;
     fn find(&self, args: Search) -> Result<Vec<Bookmark>>;
     fn edit(&mut self, args: Edit) -> Result<Bookmark>;
+    /// A bookmark along with its notes and highlights
+    fn get(&self, id: i64) -> Result<Bookmark>;
+    /// Records that bookmark `id` was opened
+    fn visit(&mut self, id: i64) -> Result<Bookmark>;
     fn tags(&self, args: Tags) -> Result<Vec<(String, usize)>>;
+    fn tag_tree(&self, args: Tags) -> Result<Vec<TagNode>>;
     fn rename_tag(&self, args: RenameTag) -> Result<usize>;
-    fn delete(&self, args: Delete) -> Result<usize>;
+    fn add_tag_alias(&self, args: TagAlias) -> Result<usize>;
+    fn tag_aliases(&self) -> Result<Vec<(String, String)>>;
+    fn tag_info(&self, name: &str) -> Result<TagInfo>;
+    /// Details of every tag, e.g. to learn their colors
+    fn tag_infos(&self) -> Result<Vec<TagInfo>>;
+    fn edit_tag(&self, args: EditTag) -> Result<TagInfo>;
+    fn delete_tag(&self, args: DeleteTag) -> Result<usize>;
+    fn merge_tags(&self, args: MergeTags) -> Result<usize>;
+    fn prune_tags(&self) -> Result<usize>;
+    fn retag(&mut self, args: Retag) -> Result<usize>;
+    fn suggest_tags(&self, args: SuggestTags) -> Result<Vec<TagSuggestion>>;
+    fn apply_rules(&mut self, args: ApplyRules) -> Result<Vec<RuleMatch>>;
+    /// Unread bookmarks, oldest first
+    fn queue(&self) -> Result<Vec<Bookmark>>;
+    fn read(&mut self, args: MarkRead) -> Result<Bookmark>;
+    fn unread(&mut self, args: MarkRead) -> Result<Bookmark>;
+    /// Deletes the bookmarks picked by `args` (none with `dry_run`), returning them. Never asks
+    /// for confirmation; that's up to the caller.
+    fn delete(&self, args: Delete) -> Result<Vec<Bookmark>>;
+    /// Deletes bookmark `id`, returning what it was
+    fn delete_bookmark(&self, id: i64) -> Result<Bookmark>;
+    /// Moves bookmark `id` to the trash, where it's left out of searches until it's restored
+    fn trash_bookmark(&mut self, id: i64) -> Result<Bookmark>;
+    /// Takes bookmark `id` back out of the trash
+    fn restore_bookmark(&mut self, id: i64) -> Result<Bookmark>;
+    /// Bookmarks in the trash, most recently trashed first
+    fn trash(&self) -> Result<Vec<Bookmark>>;
+    /// Deletes every bookmark in the trash for good, returning them
+    fn empty_trash(&self) -> Result<Vec<Bookmark>>;
 }
 
 fn wrap_db_err(err: DatabaseError) -> Error {
-    Error::RusqliteError(err)
+    match err {
+        DatabaseError::NotFound(id) => Error::NotFound(id),
+        DatabaseError::TagNotFound(name) => Error::TagNotFound(name),
+        err => Error::Storage(err),
+    }
+}
+
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    tags.iter()
+        .map(|tag| tags::normalize(tag))
+        .filter(|tag| !tag.is_empty())
+        .collect()
+}
+
+/// Refuses changes to bookmarks mirrored from another server
+fn check_writable(bookmark: &Bookmark) -> Result<()> {
+    match &bookmark.origin {
+        Some(origin) => Err(Error::ReadOnly {
+            id: bookmark.id,
+            origin: origin.to_string(),
+        }),
+        None => Ok(()),
+    }
 }
 
 pub struct DatabaseInterface {
-    db: Database,
+    db: Box<dyn Storage + Send>,
     web: WebClient,
+    rules: Vec<Rule>,
 }
 
 impl DatabaseInterface {
-    pub fn from(db: Database, web: WebClient) -> Self {
-        Self { db, web }
+    pub fn from(db: Box<dyn Storage + Send>, web: WebClient, rules: Vec<Rule>) -> Self {
+        Self { db, web, rules }
+    }
+
+    /// Treats this database as a replica of a server and syncs it; see [sync::sync]
+    pub fn sync(
+        &mut self,
+        rule: ConflictRule,
+        exchange: impl FnOnce(&SyncRequest) -> Result<SyncResponse>,
+    ) -> Result<SyncReport> {
+        sync::sync(self.db.as_mut(), rule, exchange)
+    }
+
+    /// Reads a page of the change log; see [sync::feed]
+    pub fn changes(&self, args: Changes) -> Result<ChangeFeed> {
+        sync::feed(self.db.as_ref(), args.since, args.limit).map_err(wrap_db_err)
+    }
+
+    /// Copies another server's bookmarks into this database as read-only mirrors; see
+    /// [sync::mirror]
+    pub fn mirror(
+        &mut self,
+        origin: &str,
+        tag: Option<&str>,
+        fetch: impl FnMut(i64) -> Result<ChangeFeed>,
+    ) -> Result<usize> {
+        sync::mirror(self.db.as_mut(), origin, tag, fetch)
+    }
+
+    /// Handles a sync request from a client's replica; see [sync::handle]
+    pub fn handle_sync(&mut self, request: SyncRequest) -> Result<SyncResponse> {
+        sync::handle(self.db.as_mut(), request).map_err(wrap_db_err)
+    }
+
+    /// Adds a user who can sign in to the server with `username` and `password`
+    pub fn add_user(&mut self, username: &str, password: &str) -> Result<User> {
+        if username.is_empty() || password.is_empty() {
+            return Err(Error::InvalidQuery(
+                "usernames and passwords can't be empty".to_string(),
+            ));
+        }
+        let hash = users::hash_password(password)
+            .map_err(|e| Error::InvalidQuery(format!("couldn't hash the password: {e}")))?;
+        self.db.add_user(username, &hash).map_err(wrap_db_err)
+    }
+
+    pub fn users(&self) -> Result<Vec<User>> {
+        self.db.get_users().map_err(wrap_db_err)
+    }
+
+    /// Removes a user, returning whether there was one named `username`
+    pub fn remove_user(&mut self, username: &str) -> Result<bool> {
+        self.db.delete_user(username).map_err(wrap_db_err)
+    }
+
+    /// Whether `username` is a user in the database and `password` is their password
+    pub fn check_user(&self, username: &str, password: &str) -> Result<bool> {
+        Ok(
+            match self.db.get_password_hash(username).map_err(wrap_db_err)? {
+                Some(hash) => users::verify_password(password, &hash),
+                None => false,
+            },
+        )
+    }
+
+    /// Runs a search, pulling any `tag:` filters out of the query
+    fn search(&self, args: &Search) -> Result<Vec<Bookmark>> {
+        let parsed = args.query.as_deref().map(query::parse).unwrap_or_default();
+        let mut tags = normalize_tags(&args.tags);
+        tags.extend(parsed.tags);
+        let mut bookmarks = self
+            .db
+            .search_bookmarks(&parsed.text, &tags, args.all_tags)
+            .map_err(wrap_db_err)?;
+        if let Some(unread) = parsed.unread {
+            bookmarks.retain(|b| b.unread == unread);
+        }
+        Ok(bookmarks)
+    }
+
+    /// Marks the bookmark picked by `args` read or unread
+    fn set_unread(&mut self, args: MarkRead, unread: bool) -> Result<Bookmark> {
+        let bookmark = match (args.id, args.url) {
+            (Some(id), _) => self.db.get_single_bookmark(id).map_err(wrap_db_err)?,
+            (None, Some(url)) => self
+                .db
+                .sync_state(&url)
+                .map_err(wrap_db_err)?
+                .and_then(|change| change.bookmark)
+                .ok_or(Error::UrlNotFound(url))?,
+            (None, None) => {
+                return Err(Error::InvalidQuery(
+                    "give the ID or URL of a bookmark".to_string(),
+                ))
+            }
+        };
+        check_writable(&bookmark)?;
+        self.db.set_unread(bookmark.id, unread).map_err(wrap_db_err)
     }
 }
 
 impl Interface for DatabaseInterface {
-    fn add(&mut self, args: Add) -> Result<Bookmark> {
+    fn add(&mut self, args: Add) -> Result<(Bookmark, AddOutcome)> {
         let metadata = if let Some(title) = args.title {
             Metadata {
                 title: Some(title),
                 description: None,
+                keywords: Vec::new(),
             }
         } else {
             self.web.get_metadata(&args.url).unwrap_or(Metadata {
                 title: None,
                 description: None,
+                keywords: Vec::new(),
             })
         };
-        self.db
-            .add_bookmark(&args.url, metadata, &args.tags)
-            .map_err(wrap_db_err)
+        if args.merge || args.unread {
+            let existing = self.db.sync_state(&args.url).map_err(wrap_db_err)?;
+            if let Some(bookmark) = existing.and_then(|c| c.bookmark) {
+                check_writable(&bookmark)?;
+            }
+        }
+        let mut tags = normalize_tags(&args.tags);
+        tags.extend(rules::tags_for(
+            &self.rules,
+            &args.url,
+            metadata.title.as_deref(),
+        ));
+        let (bookmark, outcome) = self
+            .db
+            .add_bookmark(
+                &args.url,
+                metadata,
+                &tags.into_iter().unique().collect_vec(),
+                args.merge,
+            )
+            .map_err(wrap_db_err)?;
+        if args.unread && !bookmark.unread {
+            let bookmark = self.db.set_unread(bookmark.id, true).map_err(wrap_db_err)?;
+            return Ok((bookmark, outcome));
+        }
+        Ok((bookmark, outcome))
     }
 
     fn find(&self, args: Search) -> Result<Vec<Bookmark>> {
-        self.db
-            .search_bookmarks(&args.query, &args.tags, args.all_tags)
-            .map_err(wrap_db_err)
+        self.search(&args)
     }
 
     // TODO: This doesn't handle transactions properly
     fn edit(&mut self, args: Edit) -> Result<Bookmark> {
+        check_writable(&self.db.get_single_bookmark(args.id).map_err(wrap_db_err)?)?;
         if !args.add_tags.is_empty() {
             self.db
-                .add_tags(args.id, &args.add_tags)
+                .add_tags(args.id, &normalize_tags(&args.add_tags))
                 .map_err(wrap_db_err)?;
         }
         if !args.remove_tags.is_empty() {
             self.db
-                .remove_tags(args.id, &args.remove_tags)
+                .remove_tags(args.id, &normalize_tags(&args.remove_tags))
+                .map_err(wrap_db_err)?;
+        }
+        if let Some(notes) = &args.notes {
+            let notes = Some(notes.trim()).filter(|notes| !notes.is_empty());
+            self.db.set_notes(args.id, notes).map_err(wrap_db_err)?;
+        }
+        for highlight_id in args.remove_highlights {
+            if !self
+                .db
+                .remove_highlight(args.id, highlight_id)
+                .map_err(wrap_db_err)?
+            {
+                return Err(Error::InvalidQuery(format!(
+                    "bookmark {} has no highlight {highlight_id}",
+                    args.id
+                )));
+            }
+        }
+        if let Some(text) = &args.highlight {
+            let comment = args.comment.as_deref().filter(|c| !c.is_empty());
+            self.db
+                .add_highlight(args.id, text, comment)
                 .map_err(wrap_db_err)?;
         }
         self.db.get_single_bookmark(args.id).map_err(wrap_db_err)
     }
 
+    fn get(&self, id: i64) -> Result<Bookmark> {
+        self.db.get_single_bookmark(id).map_err(wrap_db_err)
+    }
+
+    fn visit(&mut self, id: i64) -> Result<Bookmark> {
+        self.db.record_visit(id).map_err(wrap_db_err)
+    }
+
     fn tags(&self, args: Tags) -> Result<Vec<(String, usize)>> {
         self.db
             .get_tags(args.sort_by_count, args.reverse)
             .map_err(wrap_db_err)
     }
 
+    fn tag_tree(&self, args: Tags) -> Result<Vec<TagNode>> {
+        let pairs = self.db.get_bookmark_tags().map_err(wrap_db_err)?;
+        Ok(tags::build_tree(&pairs, args.sort_by_count, args.reverse))
+    }
+
     fn rename_tag(&self, args: RenameTag) -> Result<usize> {
+        let (from, to) = (tags::normalize(&args.from), tags::normalize(&args.to));
+        if from.is_empty() || to.is_empty() {
+            return Err(Error::InvalidQuery("tag names can't be empty".to_string()));
+        }
+        self.db.rename_tag(&from, &to).map_err(wrap_db_err)
+    }
+
+    fn add_tag_alias(&self, args: TagAlias) -> Result<usize> {
+        let (alias, tag) = (tags::normalize(&args.alias), tags::normalize(&args.tag));
+        if alias.is_empty() || tag.is_empty() {
+            return Err(Error::InvalidQuery("tag names can't be empty".to_string()));
+        }
+        if tags::is_within(&tag, &alias) {
+            return Err(Error::InvalidQuery(format!(
+                "{alias} can't be an alias of itself or a tag below it"
+            )));
+        }
+        self.db.add_tag_alias(&alias, &tag).map_err(wrap_db_err)
+    }
+
+    fn tag_aliases(&self) -> Result<Vec<(String, String)>> {
+        let mut aliases = self.db.get_tag_aliases().map_err(wrap_db_err)?;
+        aliases.sort();
+        Ok(aliases)
+    }
+
+    fn tag_info(&self, name: &str) -> Result<TagInfo> {
         self.db
-            .rename_tag(&args.from, &args.to)
+            .get_tag_info(&tags::normalize(name))
             .map_err(wrap_db_err)
     }
 
-    fn delete(&self, args: Delete) -> Result<usize> {
-        let search =
-            self.db
-                .search_bookmarks(&args.search.query, &args.search.tags, args.search.all_tags);
-        eprintln!("Search result: {:?}", search);
+    fn tag_infos(&self) -> Result<Vec<TagInfo>> {
+        self.db.get_tag_infos().map_err(wrap_db_err)
+    }
+
+    fn edit_tag(&self, args: EditTag) -> Result<TagInfo> {
+        let color = match args.color.as_deref() {
+            Some("") => Some(String::new()),
+            Some(name) => match Color::from_name(name) {
+                Some(_) => Some(name.to_lowercase()),
+                None => {
+                    return Err(Error::InvalidQuery(format!(
+                        "unknown color {name:?}; use one of {}",
+                        TAG_COLORS.iter().map(|(name, _)| *name).join(", ")
+                    )))
+                }
+            },
+            None => None,
+        };
         self.db
-            .delete_bookmarks(search.map_err(wrap_db_err)?.iter().map(|b| b.id).collect())
+            .update_tag(
+                &tags::normalize(&args.name),
+                args.description.as_deref(),
+                color.as_deref(),
+                args.pinned,
+            )
             .map_err(wrap_db_err)
     }
+
+    fn delete_tag(&self, args: DeleteTag) -> Result<usize> {
+        let tag = tags::normalize(&args.tag);
+        if tag.is_empty() {
+            return Err(Error::InvalidQuery("tag names can't be empty".to_string()));
+        }
+        self.db.delete_tag(&tag).map_err(wrap_db_err)
+    }
+
+    fn merge_tags(&self, args: MergeTags) -> Result<usize> {
+        let into = tags::normalize(&args.into);
+        let from = normalize_tags(&args.tags);
+        if into.is_empty() || from.is_empty() {
+            return Err(Error::InvalidQuery("tag names can't be empty".to_string()));
+        }
+        self.db.merge_tags(&from, &into).map_err(wrap_db_err)
+    }
+
+    fn prune_tags(&self) -> Result<usize> {
+        self.db.prune_tags().map_err(wrap_db_err)
+    }
+
+    fn retag(&mut self, args: Retag) -> Result<usize> {
+        if args.add_tags.is_empty() && args.remove_tags.is_empty() {
+            return Err(Error::InvalidQuery(
+                "give at least one tag to add or remove".to_string(),
+            ));
+        }
+        let bookmarks = self.search(&args.search)?;
+        for bookmark in &bookmarks {
+            check_writable(bookmark)?;
+        }
+        let ids: Vec<i64> = bookmarks.iter().map(|b| b.id).collect();
+        self.db
+            .retag(
+                &ids,
+                &normalize_tags(&args.add_tags),
+                &normalize_tags(&args.remove_tags),
+            )
+            .map_err(wrap_db_err)
+    }
+
+    fn suggest_tags(&self, args: SuggestTags) -> Result<Vec<TagSuggestion>> {
+        // Suggestions from the bookmarks alone are still worth having if the page can't be fetched
+        let metadata = self.web.get_metadata(&args.url).unwrap_or(Metadata {
+            title: None,
+            description: None,
+            keywords: Vec::new(),
+        });
+        let bookmarks = self
+            .db
+            .search_bookmarks(&None, &[], false)
+            .map_err(wrap_db_err)?;
+        let existing = self.db.get_tags(false, false).map_err(wrap_db_err)?;
+        let aliases = self.db.get_tag_aliases().map_err(wrap_db_err)?;
+        Ok(suggest::suggest(
+            &args.url, &metadata, &bookmarks, &existing, &aliases,
+        ))
+    }
+
+    fn apply_rules(&mut self, args: ApplyRules) -> Result<Vec<RuleMatch>> {
+        let aliases = self.db.get_tag_aliases().map_err(wrap_db_err)?;
+        let bookmarks = self
+            .db
+            .search_bookmarks(&None, &[], false)
+            .map_err(wrap_db_err)?;
+        let mut matches = Vec::new();
+        // Mirrored bookmarks are left to the server they come from
+        for bookmark in bookmarks.into_iter().filter(|b| b.origin.is_none()) {
+            let add_tags: Vec<String> =
+                rules::tags_for(&self.rules, &bookmark.url, bookmark.title.as_deref())
+                    .into_iter()
+                    .map(|tag| tags::resolve_alias(&tag, &aliases))
+                    .filter(|tag| !bookmark.tags.contains(tag))
+                    .unique()
+                    .collect();
+            if add_tags.is_empty() {
+                continue;
+            }
+            if !args.dry_run {
+                self.db
+                    .add_tags(bookmark.id, &add_tags)
+                    .map_err(wrap_db_err)?;
+            }
+            matches.push(RuleMatch { bookmark, add_tags });
+        }
+        Ok(matches)
+    }
+
+    fn queue(&self) -> Result<Vec<Bookmark>> {
+        // Search results are in ID order, which is the order they were added in
+        self.search(&Search {
+            query: Some("is:unread".to_string()),
+            tags: Vec::new(),
+            all_tags: false,
+        })
+    }
+
+    fn read(&mut self, args: MarkRead) -> Result<Bookmark> {
+        self.set_unread(args, false)
+    }
+
+    fn unread(&mut self, args: MarkRead) -> Result<Bookmark> {
+        self.set_unread(args, true)
+    }
+
+    fn delete(&self, args: Delete) -> Result<Vec<Bookmark>> {
+        let empty_search = args
+            .search
+            .query
+            .as_deref()
+            .is_none_or(|query| query.trim().is_empty())
+            && args.search.tags.is_empty();
+        let bookmarks = if !args.ids.is_empty() {
+            if !empty_search {
+                return Err(Error::InvalidQuery(
+                    "give either IDs or a search, not both".to_string(),
+                ));
+            }
+            args.ids
+                .iter()
+                .unique()
+                .map(|&id| self.db.get_single_bookmark(id).map_err(wrap_db_err))
+                .collect::<Result<Vec<_>>>()?
+        } else if empty_search && !args.all {
+            return Err(Error::InvalidQuery(
+                "an empty search matches every bookmark; use --all to delete them all".to_string(),
+            ));
+        } else {
+            self.search(&args.search)?
+        };
+        for bookmark in &bookmarks {
+            check_writable(bookmark)?;
+        }
+        if !args.dry_run && !bookmarks.is_empty() {
+            self.db
+                .delete_bookmarks(bookmarks.iter().map(|b| b.id).collect())
+                .map_err(wrap_db_err)?;
+        }
+        Ok(bookmarks)
+    }
+
+    fn delete_bookmark(&self, id: i64) -> Result<Bookmark> {
+        let bookmark = self.db.get_single_bookmark(id).map_err(wrap_db_err)?;
+        check_writable(&bookmark)?;
+        self.db.delete_bookmarks(vec![id]).map_err(wrap_db_err)?;
+        Ok(bookmark)
+    }
+
+    fn trash_bookmark(&mut self, id: i64) -> Result<Bookmark> {
+        check_writable(&self.db.get_single_bookmark(id).map_err(wrap_db_err)?)?;
+        self.db.set_trashed(id, true).map_err(wrap_db_err)
+    }
+
+    fn restore_bookmark(&mut self, id: i64) -> Result<Bookmark> {
+        self.db.set_trashed(id, false).map_err(wrap_db_err)
+    }
+
+    fn trash(&self) -> Result<Vec<Bookmark>> {
+        self.db.get_trash().map_err(wrap_db_err)
+    }
+
+    fn empty_trash(&self) -> Result<Vec<Bookmark>> {
+        let bookmarks = self.db.get_trash().map_err(wrap_db_err)?;
+        self.db
+            .delete_bookmarks(bookmarks.iter().map(|b| b.id).collect())
+            .map_err(wrap_db_err)?;
+        Ok(bookmarks)
+    }
 }
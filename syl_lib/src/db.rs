@@ -1,31 +1,162 @@
 use core::fmt;
+use std::collections::HashMap;
+use std::error;
 use std::fmt::{Display, Formatter};
 
 use itertools::Itertools;
-use rusqlite::{params_from_iter, Connection, Result, Row, ToSql, Transaction};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ValueRef};
+use rusqlite::{params_from_iter, Connection, OptionalExtension, Row, ToSql, Transaction};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    colors::{color, Color},
+    colors::{self, color, Color},
     migrations::MIGRATIONS,
+    storage::Storage,
+    sync::{now, Change, ChangeKind},
+    tags::{self, TagInfo},
     web::Metadata,
 };
 
-pub use rusqlite::Error;
+#[derive(Debug)]
+pub enum Error {
+    Sqlite(rusqlite::Error),
+    #[cfg(feature = "postgres")]
+    Postgres(postgres::Error),
+    /// No bookmark exists with the given ID
+    NotFound(i64),
+    /// No tag exists with the given name
+    TagNotFound(String),
+    /// The database_version in syl_meta is missing or isn't a number
+    InvalidVersion(Option<String>),
+    /// The configured backend isn't available in this build
+    Unsupported(&'static str),
+    /// The change log contains a kind of change this version of syl doesn't know about
+    InvalidChangeKind(String),
+    /// A user with the given name already exists
+    UserExists(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Sqlite(e) => write!(f, "{e}"),
+            #[cfg(feature = "postgres")]
+            Error::Postgres(e) => write!(f, "{e}"),
+            Error::NotFound(id) => write!(f, "no bookmark with ID {id} exists"),
+            Error::TagNotFound(name) => write!(f, "no tag named {name} exists"),
+            Error::InvalidVersion(Some(version)) => write!(
+                f,
+                "invalid database version {version:?}; your database may be corrupt"
+            ),
+            Error::InvalidVersion(None) => {
+                write!(f, "missing database version; your database may be corrupt")
+            }
+            Error::Unsupported(feature) => {
+                write!(f, "syl was built without support for {feature}")
+            }
+            Error::InvalidChangeKind(kind) => write!(f, "unknown kind of change {kind:?}"),
+            Error::UserExists(name) => write!(f, "a user named {name} already exists"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Sqlite(e) => e.source(),
+            #[cfg(feature = "postgres")]
+            Error::Postgres(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Error::Sqlite(error)
+    }
+}
+
+#[cfg(feature = "postgres")]
+impl From<postgres::Error> for Error {
+    fn from(error: postgres::Error) -> Self {
+        Error::Postgres(error)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bookmark {
     pub id: i64,
     pub url: String,
     pub title: Option<String>,
     pub description: Option<String>,
     pub tags: Vec<String>,
+    /// The server this bookmark is mirrored from, if it's a read-only copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Whether the bookmark is in the read-later queue
+    #[serde(default)]
+    pub unread: bool,
+    /// When the bookmark was last marked read, in milliseconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_at: Option<i64>,
+    /// The user's own notes in Markdown, kept apart from the description fetched from the page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub highlights: Vec<Highlight>,
+    /// When the bookmark was added to this database, in milliseconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<i64>,
+    /// When the bookmark was last opened with `syl open`. Visits are counted separately by each
+    /// database and aren't synced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_visited: Option<i64>,
+    #[serde(default)]
+    pub visit_count: i64,
+    /// When the bookmark was moved to the trash, in milliseconds since the Unix epoch. Trashed
+    /// bookmarks are left out of searches and tag counts until they're restored. Each database
+    /// keeps its own trash, so this isn't synced either; emptying the trash is what deletes them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<i64>,
+}
+
+/// A passage quoted from a bookmarked page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Highlight {
+    /// Only meaningful within one database; sync gives highlights new IDs
+    pub id: i64,
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Someone who can sign in to the server, besides the user in its config
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    /// When the user was added, in milliseconds since the Unix epoch
+    pub created_at: i64,
+}
+
+/// What [Database::add_bookmark] did with the URL it was given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddOutcome {
+    Created,
+    /// A bookmark for the URL already existed and was left untouched
+    AlreadyExists,
+    /// A bookmark for the URL already existed and the new tags were added to it
+    Merged,
 }
 
 const CURRENT_VERSION: usize = 0;
 
 impl Bookmark {
-    fn from_row(row: &Row<'_>) -> Result<Self> {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
         Ok(Bookmark {
             id: row.get(0)?,
             url: row.get(1)?,
@@ -36,20 +167,37 @@ impl Bookmark {
             } else {
                 Vec::new()
             },
+            origin: row.get(5)?,
+            unread: row.get(6)?,
+            read_at: row.get(7)?,
+            notes: row.get(8)?,
+            highlights: Vec::new(),
+            created_at: row.get(9)?,
+            last_visited: row.get(10)?,
+            visit_count: row.get(11)?,
+            deleted_at: row.get(12)?,
+        })
+    }
+
+    /// Reads a change log row: the columns of a bookmark (all NULL if it's been deleted), followed
+    /// by seq, kind, url, version and changed_at
+    fn change_from_row(row: &Row<'_>) -> rusqlite::Result<Change> {
+        Ok(Change {
+            seq: row.get(13)?,
+            kind: row.get(14)?,
+            url: row.get(15)?,
+            version: row.get(16)?,
+            changed_at: row.get(17)?,
+            bookmark: match row.get::<_, Option<i64>>(0)? {
+                Some(_) => Some(Bookmark::from_row(row)?),
+                None => None,
+            },
         })
     }
 
     fn format_tags(&self) -> String {
-        if self.tags.len() > 0 {
-            format!(
-                "[{}]",
-                &self
-                    .tags
-                    .iter()
-                    .map(|t| color(t, Color::Yellow))
-                    .intersperse(",".to_string())
-                    .collect::<String>()
-            )
+        if !self.tags.is_empty() {
+            format!("[{}]", &self.tags.iter().map(|t| colors::tag(t)).join(","))
         } else {
             String::from("")
         }
@@ -60,6 +208,16 @@ impl Bookmark {
     }
 }
 
+impl User {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(User {
+            id: row.get(0)?,
+            username: row.get(1)?,
+            created_at: row.get(2)?,
+        })
+    }
+}
+
 impl Display for Bookmark {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "[{}]", color(&self.id.to_string(), Color::Blue))?;
@@ -68,11 +226,21 @@ impl Display for Bookmark {
         } else {
             write!(f, " {}", color(&self.url, Color::BoldGreen))?;
         }
-        writeln!(f, " {}", self.format_tags())?;
+        write!(f, " {}", self.format_tags())?;
+        if self.unread {
+            if !self.tags.is_empty() {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", color("(unread)", Color::Magenta))?;
+        }
+        writeln!(f)?;
         self.write_url(f)?;
         if let Some(description) = &self.description {
             write!(f, "\n{}", description)?;
         };
+        if let Some(origin) = &self.origin {
+            write!(f, "\nMirrored from {}", color(origin, Color::Cyan))?;
+        }
         Ok(())
     }
 }
@@ -85,21 +253,26 @@ impl Database {
     pub fn open(path: &str) -> Result<Self> {
         let connection = Connection::open(path)?;
         let db = Database { connection };
-        match db.connection.query_row(
-            "SELECT value FROM syl_meta WHERE key='database_version'",
+        let initialized: bool = db.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'syl_meta')",
             (),
-            |row| row.get::<usize, String>(0),
-        ) {
-            Ok(db_version) => {
-                if let Ok(version) = db_version.parse::<usize>() {
-                    db.migrate(version)?;
-                } else {
-                    panic!("Error parsing database version number! Your database may be corrupt.");
-                }
-            }
-            Err(_) => {
-                db.initialize()?;
-            }
+            |row| row.get(0),
+        )?;
+        if !initialized {
+            db.initialize()?;
+            return Ok(db);
+        }
+        let db_version = db
+            .connection
+            .query_row(
+                "SELECT value FROM syl_meta WHERE key='database_version'",
+                (),
+                |row| row.get::<usize, String>(0),
+            )
+            .optional()?;
+        match db_version.as_deref().map(str::parse::<usize>) {
+            Some(Ok(version)) => db.migrate(version)?,
+            _ => return Err(Error::InvalidVersion(db_version)),
         }
         Ok(db)
     }
@@ -140,150 +313,479 @@ impl Database {
         Ok(())
     }
 
-    pub fn add_bookmark(
+    fn migrate(&self, current_version: usize) -> Result<()> {
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(current_version) {
+            eprintln!("Migrating database to version {}", i + 1);
+            self.connection.execute_batch(&format!(
+                "
+                BEGIN;
+                {migration}
+                UPDATE syl_meta SET value = '{}' WHERE key = 'database_version';
+                COMMIT;
+                ",
+                i + 1
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromSql for ChangeKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        ChangeKind::parse(value.as_str()?).ok_or(FromSqlError::InvalidType)
+    }
+}
+
+/// Bumps the version of bookmark `id` and records the change in the change log. Call this after
+/// making the change, or before deleting the bookmark.
+fn log_change(tx: &Transaction, id: i64, kind: ChangeKind) -> rusqlite::Result<()> {
+    tx.execute(
+        "UPDATE bookmark SET version = version + 1, updated_at = ? WHERE id = ?",
+        (now(), id),
+    )?;
+    tx.execute(
+        "INSERT INTO change_log (url, kind, version, changed_at)
+        SELECT url, ?, version, updated_at FROM bookmark WHERE id = ?",
+        (kind.as_str(), id),
+    )?;
+    Ok(())
+}
+
+const SELECT_CHANGE: &str = "
+    SELECT id, bookmark.url, title, description, group_concat(tag_name), origin, unread, read_at,
+        notes, CAST(strftime('%s', created_at) AS INTEGER) * 1000,
+        last_visited, visit_count, deleted_at,
+        seq, kind, change_log.url, change_log.version, changed_at
+    FROM change_log
+    LEFT JOIN bookmark ON bookmark.url = change_log.url
+    LEFT JOIN bookmark_tag ON bookmark_tag.bookmark_id = bookmark.id";
+
+impl Storage for Database {
+    fn add_bookmark(
         &mut self,
         url: &str,
         metadata: Metadata,
-        tags: &Vec<String>,
-    ) -> Result<Bookmark> {
+        tags: &[String],
+        merge: bool,
+    ) -> Result<(Bookmark, AddOutcome)> {
+        let tags = &resolve_aliases(&self.connection, tags)?;
         let tx = self.connection.transaction()?;
-        let bookmark = tx.query_row(
+        let existing = tx.query_row(
             "
-            SELECT id, url, title, description, group_concat(tag_name)
+            SELECT id, url, title, description, group_concat(tag_name), origin, unread, read_at,
+                notes, CAST(strftime('%s', created_at) AS INTEGER) * 1000,
+                last_visited, visit_count, deleted_at
             FROM bookmark
             LEFT JOIN bookmark_tag ON bookmark_tag.bookmark_id = bookmark.id
             WHERE url = ?
-            GROUP BY tag_name
+            GROUP BY id
             ",
             [url],
             Bookmark::from_row,
         );
-        match bookmark {
-            Ok(bookmark) => {
-                println!("A bookmark for that URL already exists:");
-                Ok(bookmark)
+        match existing {
+            Ok(mut bookmark) => {
+                load_highlights(&tx, [&mut bookmark])?;
+                let new_tags: Vec<String> = tags
+                    .iter()
+                    .filter(|t| !bookmark.tags.contains(t))
+                    .cloned()
+                    .collect();
+                if bookmark.deleted_at.take().is_some() {
+                    // Adding it again takes it back out of the trash
+                    tx.execute(
+                        "UPDATE bookmark SET deleted_at = NULL WHERE id = ?",
+                        [bookmark.id],
+                    )?;
+                }
+                if !merge || new_tags.is_empty() {
+                    tx.commit()?;
+                    return Ok((bookmark, AddOutcome::AlreadyExists));
+                }
+                add_tags(&tx, bookmark.id, &new_tags)?;
+                log_change(&tx, bookmark.id, ChangeKind::Tags)?;
+                tx.commit()?;
+                bookmark.tags.extend(new_tags);
+                Ok((bookmark, AddOutcome::Merged))
             }
-            Err(Error::QueryReturnedNoRows) => {
-                println!("Added bookmark:");
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                // created_at is stored to the second
+                let created_at = now() / 1000 * 1000;
                 tx.execute(
-                    "INSERT INTO bookmark (url, title, description, created_at)
-                    VALUES (?, ?, ?, datetime('now'))",
-                    (url, &metadata.title, &metadata.description),
+                    "INSERT INTO bookmark (url, title, description, created_at, version)
+                    VALUES (?, ?, ?, datetime(? / 1000, 'unixepoch'), 0)",
+                    (url, &metadata.title, &metadata.description, created_at),
                 )?;
                 let id = tx.last_insert_rowid();
                 add_tags(&tx, id, tags)?;
+                log_change(&tx, id, ChangeKind::Insert)?;
                 tx.commit()?;
-                Ok(Bookmark {
-                    id,
-                    url: url.to_string(),
-                    title: metadata.title,
-                    description: metadata.description,
-                    tags: tags.to_vec(),
-                })
-            }
-            Err(e) => {
-                panic!("Error adding new bookmark: {}", e);
+                Ok((
+                    Bookmark {
+                        id,
+                        url: url.to_string(),
+                        title: metadata.title,
+                        description: metadata.description,
+                        tags: tags.to_vec(),
+                        origin: None,
+                        unread: false,
+                        read_at: None,
+                        notes: None,
+                        highlights: Vec::new(),
+                        created_at: Some(created_at),
+                        last_visited: None,
+                        visit_count: 0,
+                        deleted_at: None,
+                    },
+                    AddOutcome::Created,
+                ))
             }
+            Err(e) => Err(e.into()),
         }
     }
 
-    pub fn search_bookmarks(
+    fn search_bookmarks(
         &self,
         query: &Option<String>,
-        tags: &Vec<String>,
+        tags: &[String],
         all_tags: bool,
     ) -> Result<Vec<Bookmark>> {
         // TODO: Come up with some ranking/ordering. Perhaps:
         // https://www.sqlite.org/fts3.html
         let mut select = String::from(
-            "SELECT id, url, title, description, group_concat(tag_name)
+            "SELECT id, url, title, description, group_concat(tag_name), origin, unread, read_at,
+                notes, CAST(strftime('%s', created_at) AS INTEGER) * 1000,
+                last_visited, visit_count, deleted_at
             FROM bookmark
             LEFT JOIN bookmark_tag ON bookmark_tag.bookmark_id = bookmark.id
-            WHERE 1",
+            WHERE deleted_at IS NULL",
         );
+        let tags = resolve_aliases(&self.connection, tags)?;
         let mut params: Vec<&dyn ToSql> = Vec::new();
         if let Some(query) = query {
             select += " AND (
-                url LIKE '%' || ? || '%'
-                OR title LIKE '%' || ? || '%'
-                OR description LIKE '%' || ? || '%'
+                url LIKE '%' || ?1 || '%'
+                OR title LIKE '%' || ?1 || '%'
+                OR description LIKE '%' || ?1 || '%'
+                OR notes LIKE '%' || ?1 || '%'
+                OR EXISTS (SELECT 1 FROM highlight h WHERE h.bookmark_id = bookmark.id
+                    AND (h.text LIKE '%' || ?1 || '%' OR h.comment LIKE '%' || ?1 || '%'))
             )";
-            params.extend_from_slice(&[query, query, query]);
-        }
-        if tags.len() > 0 {
-            select += &format!(
-                " AND id IN (SELECT bookmark_id FROM bookmark_tag WHERE tag_name IN ({})",
-                repeat_vars(tags.len()),
-            );
-            if all_tags {
-                select += &format!(
-                    " GROUP BY bookmark_id HAVING count(bookmark_id) = {}",
-                    tags.len()
-                );
-            }
+            params.push(query);
+        }
+        if !tags.is_empty() {
+            // A tag matches itself and everything below it, e.g. lang/rust matches lang/rust/async
+            let matches_tag =
+                " EXISTS (SELECT 1 FROM bookmark_tag bt WHERE bt.bookmark_id = bookmark.id
+                AND (bt.tag_name = ? OR substr(bt.tag_name, 1, length(?) + 1) = ? || '/'))";
+            let joiner = if all_tags { " AND" } else { " OR" };
+            select += " AND (";
+            select += &vec![matches_tag; tags.len()].join(joiner);
             select += ")";
-            for tag in tags {
-                params.push(tag as &dyn ToSql);
+            for tag in &tags {
+                params.extend_from_slice(&[tag as &dyn ToSql, tag, tag]);
             }
         }
-        select += " GROUP BY id";
+        select += " GROUP BY id ORDER BY id";
         let mut stmt = self.connection.prepare(&select)?;
-        let bookmarks = stmt
+        let mut bookmarks: Vec<Bookmark> = stmt
             .query_map(&params[..], Bookmark::from_row)?
-            .map(|b| b.unwrap())
-            .collect();
+            .collect::<rusqlite::Result<_>>()?;
+        load_highlights(&self.connection, &mut bookmarks)?;
         Ok(bookmarks)
     }
 
-    pub fn rename_tag(&self, from: &str, to: &str) -> Result<usize> {
+    fn rename_tag(&self, from: &str, to: &str) -> Result<usize> {
+        let tx = self.connection.unchecked_transaction()?;
+        let to = resolve_aliases(&tx, &[to.to_string()])?.remove(0);
+        let ids = rename_tag(&tx, from, &to)?;
+        let renamed = log_tag_changes(&tx, ids)?;
+        tx.commit()?;
+        Ok(renamed)
+    }
+
+    fn merge_tags(&self, from: &[String], into: &str) -> Result<usize> {
+        let tx = self.connection.unchecked_transaction()?;
+        let into = resolve_aliases(&tx, &[into.to_string()])?.remove(0);
+        let mut ids = Vec::new();
+        for tag in from {
+            ids.extend(rename_tag(&tx, tag, &into)?);
+        }
+        let merged = log_tag_changes(&tx, ids)?;
+        tx.commit()?;
+        Ok(merged)
+    }
+
+    fn delete_tag(&self, tag: &str) -> Result<usize> {
+        let tx = self.connection.unchecked_transaction()?;
+        let tag = resolve_aliases(&tx, &[tag.to_string()])?.remove(0);
+        let pairs = tagged_within(&tx, &tag)?;
+        for (id, tag) in &pairs {
+            remove_tags(&tx, *id, std::slice::from_ref(tag))?;
+        }
+        let old_tags: Vec<String> = pairs.iter().map(|(_, tag)| tag.clone()).collect();
+        drop_unused_tags(&tx, &old_tags)?;
+        // Aliases would otherwise quietly bring the tag back
+        for (alias, target) in get_tag_aliases(&tx)? {
+            if tags::is_within(&target, &tag) {
+                tx.execute("DELETE FROM tag_alias WHERE alias = ?", [alias])?;
+            }
+        }
+        let deleted = log_tag_changes(&tx, pairs.into_iter().map(|(id, _)| id))?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    fn prune_tags(&self) -> Result<usize> {
+        Ok(self.connection.execute(
+            "DELETE FROM tag WHERE name NOT IN (SELECT tag_name FROM bookmark_tag)
+            AND description IS NULL AND color IS NULL AND NOT pinned",
+            [],
+        )?)
+    }
+
+    fn retag(&mut self, ids: &[i64], add: &[String], remove: &[String]) -> Result<usize> {
+        let add = &resolve_aliases(&self.connection, add)?;
+        let remove = &resolve_aliases(&self.connection, remove)?;
+        let tx = self.connection.transaction()?;
+        for &id in ids {
+            add_tags(&tx, id, add)?;
+            remove_tags(&tx, id, remove)?;
+        }
+        let retagged = log_tag_changes(&tx, ids.iter().copied())?;
+        tx.commit()?;
+        Ok(retagged)
+    }
+
+    fn add_tag_alias(&self, alias: &str, tag: &str) -> Result<usize> {
+        let tx = self.connection.unchecked_transaction()?;
+        let tag = resolve_aliases(&tx, &[tag.to_string()])?.remove(0);
+        tx.execute(
+            "INSERT INTO tag_alias (alias, tag) VALUES (?, ?)
+            ON CONFLICT (alias) DO UPDATE SET tag = excluded.tag",
+            [alias, &tag],
+        )?;
+        // This also points aliases of the alias straight at the canonical tag
+        let ids = rename_tag(&tx, alias, &tag)?;
+        let renamed = log_tag_changes(&tx, ids)?;
+        tx.commit()?;
+        Ok(renamed)
+    }
+
+    fn get_tag_aliases(&self) -> Result<Vec<(String, String)>> {
+        Ok(get_tag_aliases(&self.connection)?)
+    }
+
+    fn get_tag_info(&self, name: &str) -> Result<TagInfo> {
+        let name = resolve_aliases(&self.connection, &[name.to_string()])?.remove(0);
+        self.connection
+            .query_row(
+                &format!("{SELECT_TAG_INFO} WHERE name = ?"),
+                [&name],
+                tag_info_from_row,
+            )
+            .optional()?
+            .ok_or(Error::TagNotFound(name))
+    }
+
+    fn get_tag_infos(&self) -> Result<Vec<TagInfo>> {
         let mut stmt = self
             .connection
-            .prepare("SELECT count(name) FROM tag WHERE name = ?")?;
-        let found: isize = stmt.query_row(&[to], |r| r.get(0))?;
-        if found == 0 {
-            self.connection
-                .execute("INSERT INTO tag VALUES (?)", &[to])?;
+            .prepare(&format!("{SELECT_TAG_INFO} ORDER BY pinned DESC, name"))?;
+        let tags = stmt
+            .query_map([], tag_info_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(tags)
+    }
+
+    fn update_tag(
+        &self,
+        name: &str,
+        description: Option<&str>,
+        color: Option<&str>,
+        pinned: Option<bool>,
+    ) -> Result<TagInfo> {
+        let name = self.get_tag_info(name)?.name;
+        let tx = self.connection.unchecked_transaction()?;
+        if let Some(description) = description {
+            tx.execute(
+                "UPDATE tag SET description = NULLIF(?, '') WHERE name = ?",
+                [description, &name],
+            )?;
         }
-        self.connection.execute(
-            "UPDATE bookmark_tag SET tag_name = ? WHERE tag_name = ?",
-            &[to, from],
-        )
+        if let Some(color) = color {
+            tx.execute(
+                "UPDATE tag SET color = NULLIF(?, '') WHERE name = ?",
+                [color, &name],
+            )?;
+        }
+        if let Some(pinned) = pinned {
+            tx.execute("UPDATE tag SET pinned = ? WHERE name = ?", (pinned, &name))?;
+        }
+        tx.commit()?;
+        self.get_tag_info(&name)
     }
 
-    pub fn get_single_bookmark(&self, id: i64) -> Result<Bookmark> {
-        self.connection.query_row(
-            "
-            SELECT id, url, title, description, group_concat(tag_name)
+    fn get_bookmark_tags(&self) -> Result<Vec<(i64, String)>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT bookmark_id, tag_name FROM bookmark_tag
+                JOIN bookmark ON bookmark.id = bookmark_id
+                WHERE deleted_at IS NULL",
+        )?;
+        let pairs = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(pairs)
+    }
+
+    fn get_single_bookmark(&self, id: i64) -> Result<Bookmark> {
+        let mut bookmark = self
+            .connection
+            .query_row(
+                "
+            SELECT id, url, title, description, group_concat(tag_name), origin, unread, read_at,
+                notes, CAST(strftime('%s', created_at) AS INTEGER) * 1000,
+                last_visited, visit_count, deleted_at
             FROM bookmark
             LEFT JOIN bookmark_tag ON bookmark_tag.bookmark_id = bookmark.id
             WHERE id = ?
+            GROUP BY id
             ",
-            [&id],
-            Bookmark::from_row,
-        )
+                [&id],
+                Bookmark::from_row,
+            )
+            .optional()?
+            .ok_or(Error::NotFound(id))?;
+        load_highlights(&self.connection, [&mut bookmark])?;
+        Ok(bookmark)
+    }
+
+    fn bookmark_exists(&self, id: i64) -> Result<bool> {
+        Ok(self.connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM bookmark WHERE id = ?)",
+            [id],
+            |row| row.get(0),
+        )?)
+    }
+
+    fn set_unread(&mut self, id: i64, unread: bool) -> Result<Bookmark> {
+        let tx = self.connection.transaction()?;
+        let changed = tx.execute(
+            "UPDATE bookmark SET unread = ?1, read_at = CASE WHEN ?1 THEN read_at ELSE ?2 END
+            WHERE id = ?3",
+            (unread, now(), id),
+        )?;
+        if changed == 0 {
+            return Err(Error::NotFound(id));
+        }
+        log_change(&tx, id, ChangeKind::Update)?;
+        tx.commit()?;
+        self.get_single_bookmark(id)
+    }
+
+    fn set_trashed(&mut self, id: i64, trashed: bool) -> Result<Bookmark> {
+        let changed = self.connection.execute(
+            "UPDATE bookmark SET deleted_at = CASE WHEN ?1 THEN coalesce(deleted_at, ?2) END
+            WHERE id = ?3",
+            (trashed, now(), id),
+        )?;
+        if changed == 0 {
+            return Err(Error::NotFound(id));
+        }
+        self.get_single_bookmark(id)
+    }
+
+    fn get_trash(&self) -> Result<Vec<Bookmark>> {
+        let mut stmt = self.connection.prepare(
+            "
+            SELECT id, url, title, description, group_concat(tag_name), origin, unread, read_at,
+                notes, CAST(strftime('%s', created_at) AS INTEGER) * 1000,
+                last_visited, visit_count, deleted_at
+            FROM bookmark
+            LEFT JOIN bookmark_tag ON bookmark_tag.bookmark_id = bookmark.id
+            WHERE deleted_at IS NOT NULL
+            GROUP BY id
+            ORDER BY deleted_at DESC, id
+            ",
+        )?;
+        let mut bookmarks: Vec<Bookmark> = stmt
+            .query_map([], Bookmark::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        load_highlights(&self.connection, &mut bookmarks)?;
+        Ok(bookmarks)
+    }
+
+    fn record_visit(&mut self, id: i64) -> Result<Bookmark> {
+        let changed = self.connection.execute(
+            "UPDATE bookmark SET last_visited = ?, visit_count = visit_count + 1 WHERE id = ?",
+            (now(), id),
+        )?;
+        if changed == 0 {
+            return Err(Error::NotFound(id));
+        }
+        self.get_single_bookmark(id)
+    }
+
+    fn set_notes(&mut self, id: i64, notes: Option<&str>) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        if tx.execute("UPDATE bookmark SET notes = ? WHERE id = ?", (notes, id))? == 0 {
+            return Err(Error::NotFound(id));
+        }
+        log_change(&tx, id, ChangeKind::Update)?;
+        Ok(tx.commit()?)
+    }
+
+    fn add_highlight(&mut self, id: i64, text: &str, comment: Option<&str>) -> Result<Highlight> {
+        if !self.bookmark_exists(id)? {
+            return Err(Error::NotFound(id));
+        }
+        let tx = self.connection.transaction()?;
+        let highlight = insert_highlight(&tx, id, text, comment)?;
+        log_change(&tx, id, ChangeKind::Update)?;
+        tx.commit()?;
+        Ok(highlight)
+    }
+
+    fn remove_highlight(&mut self, id: i64, highlight_id: i64) -> Result<bool> {
+        let tx = self.connection.transaction()?;
+        let removed = tx.execute(
+            "DELETE FROM highlight WHERE id = ? AND bookmark_id = ?",
+            (highlight_id, id),
+        )? > 0;
+        if removed {
+            log_change(&tx, id, ChangeKind::Update)?;
+        }
+        tx.commit()?;
+        Ok(removed)
     }
 
-    pub fn add_tags(&mut self, id: i64, tags: &Vec<String>) -> Result<()> {
+    fn add_tags(&mut self, id: i64, tags: &[String]) -> Result<()> {
+        let tags = &resolve_aliases(&self.connection, tags)?;
         let tx = self.connection.transaction()?;
-        add_tags(&tx, id, &tags)?;
-        tx.commit()
+        add_tags(&tx, id, tags)?;
+        log_change(&tx, id, ChangeKind::Tags)?;
+        Ok(tx.commit()?)
     }
 
-    pub fn remove_tags(&mut self, id: i64, tags: &Vec<String>) -> Result<()> {
+    fn remove_tags(&mut self, id: i64, tags: &[String]) -> Result<()> {
+        let tags = &resolve_aliases(&self.connection, tags)?;
         let tx = self.connection.transaction()?;
-        remove_tags(&tx, id, &tags)?;
-        tx.commit()
+        remove_tags(&tx, id, tags)?;
+        log_change(&tx, id, ChangeKind::Tags)?;
+        Ok(tx.commit()?)
     }
 
-    pub fn get_tags(&self, sort_by_count: bool, reverse: bool) -> Result<Vec<(String, usize)>> {
+    fn get_tags(&self, sort_by_count: bool, reverse: bool) -> Result<Vec<(String, usize)>> {
         let mut stmt = self.connection.prepare(
             format!(
                 "
                 SELECT name, count(bookmark_id) as count
                 FROM tag
                 JOIN bookmark_tag ON bookmark_tag.tag_name=name
+                JOIN bookmark ON bookmark.id = bookmark_id
+                WHERE deleted_at IS NULL
                 GROUP BY name
-                ORDER BY {} {}
+                ORDER BY pinned DESC, {} {}
                 ",
                 if sort_by_count { "count" } else { "name" },
                 if reverse { "DESC" } else { "ASC" },
@@ -294,36 +796,242 @@ impl Database {
             .query_map([], |row| {
                 Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?))
             })?
-            .map(|t| t.unwrap())
-            .collect();
+            .collect::<rusqlite::Result<_>>()?;
         Ok(tags)
     }
 
-    pub fn delete_bookmarks(&self, ids: Vec<i64>) -> Result<usize> {
-        self.connection.execute(
+    fn delete_bookmarks(&self, ids: Vec<i64>) -> Result<usize> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let tx = self.connection.unchecked_transaction()?;
+        for id in &ids {
+            log_change(&tx, *id, ChangeKind::Delete)?;
+        }
+        for table in ["bookmark_tag", "highlight"] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {table} WHERE bookmark_id IN ({})",
+                    repeat_vars(ids.len())
+                ),
+                params_from_iter(&ids),
+            )?;
+        }
+        let deleted = tx.execute(
             &format!(
-                "DELETE FROM bookmark_tag WHERE bookmark_id IN ({})",
+                "DELETE FROM bookmark WHERE id IN ({})",
                 repeat_vars(ids.len())
             ),
             params_from_iter(&ids),
         )?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    fn get_meta(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .connection
+            .query_row("SELECT value FROM syl_meta WHERE key = ?", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    fn set_meta(&self, key: &str, value: &str) -> Result<()> {
         self.connection.execute(
-            &format!(
-                "DELETE FROM bookmark WHERE id IN ({})",
-                repeat_vars(ids.len())
-            ),
-            params_from_iter(&ids),
-        )
+            "INSERT INTO syl_meta (key, value) VALUES (?, ?)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            [key, value],
+        )?;
+        Ok(())
     }
 
-    fn migrate(&self, current_version: usize) -> Result<()> {
-        if current_version < MIGRATIONS.len() {
-            for i in current_version..MIGRATIONS.len() {
-                eprintln!("Migrating database to version {}", i + 1);
-                self.connection.execute_batch(MIGRATIONS[i])?;
+    fn add_user(&mut self, username: &str, password_hash: &str) -> Result<User> {
+        self.connection
+            .query_row(
+                "INSERT INTO user_account (username, password_hash, created_at) VALUES (?, ?, ?)
+                ON CONFLICT (username) DO NOTHING
+                RETURNING id, username, created_at",
+                (username, password_hash, now()),
+                User::from_row,
+            )
+            .optional()?
+            .ok_or_else(|| Error::UserExists(username.to_string()))
+    }
+
+    fn get_users(&self) -> Result<Vec<User>> {
+        let mut stmt = self
+            .connection
+            .prepare("SELECT id, username, created_at FROM user_account ORDER BY username")?;
+        let users = stmt
+            .query_map([], User::from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(users)
+    }
+
+    fn get_password_hash(&self, username: &str) -> Result<Option<String>> {
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT password_hash FROM user_account WHERE username = ?",
+                [username],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn delete_user(&self, username: &str) -> Result<bool> {
+        let deleted = self
+            .connection
+            .execute("DELETE FROM user_account WHERE username = ?", [username])?;
+        Ok(deleted > 0)
+    }
+
+    fn changes_since(&self, since: i64, limit: Option<usize>) -> Result<Vec<Change>> {
+        let mut stmt = self.connection.prepare(&format!(
+            "{SELECT_CHANGE} WHERE seq > ? GROUP BY seq ORDER BY seq LIMIT ?"
+        ))?;
+        // A negative LIMIT means no limit
+        let limit = limit.map_or(-1, |l| l as i64);
+        let mut changes: Vec<Change> = stmt
+            .query_map((since, limit), Bookmark::change_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        load_highlights(
+            &self.connection,
+            changes.iter_mut().filter_map(|c| c.bookmark.as_mut()),
+        )?;
+        Ok(changes)
+    }
+
+    fn last_change_seq(&self) -> Result<i64> {
+        Ok(self.connection.query_row(
+            "SELECT coalesce(max(seq), 0) FROM change_log",
+            (),
+            |row| row.get(0),
+        )?)
+    }
+
+    fn sync_state(&self, url: &str) -> Result<Option<Change>> {
+        let live = self
+            .connection
+            .query_row(
+                "
+                SELECT id, url, title, description, group_concat(tag_name), origin, unread, read_at,
+                    notes, CAST(strftime('%s', created_at) AS INTEGER) * 1000, last_visited,
+                    visit_count, deleted_at, version, updated_at
+                FROM bookmark
+                LEFT JOIN bookmark_tag ON bookmark_tag.bookmark_id = bookmark.id
+                WHERE url = ?
+                GROUP BY id
+                ",
+                [url],
+                |row| {
+                    Ok(Change {
+                        seq: 0,
+                        kind: ChangeKind::Update,
+                        url: url.to_string(),
+                        version: row.get(13)?,
+                        changed_at: row.get(14)?,
+                        bookmark: Some(Bookmark::from_row(row)?),
+                    })
+                },
+            )
+            .optional()?;
+        if let Some(mut change) = live {
+            load_highlights(&self.connection, change.bookmark.as_mut())?;
+            return Ok(Some(change));
+        }
+        Ok(self
+            .connection
+            .query_row(
+                &format!(
+                    "{SELECT_CHANGE} WHERE change_log.url = ? AND kind = 'delete'
+                    GROUP BY seq ORDER BY seq DESC LIMIT 1"
+                ),
+                [url],
+                Bookmark::change_from_row,
+            )
+            .optional()?)
+    }
+
+    fn write_change(&mut self, change: &Change, record: bool) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        let id: Option<i64> = tx
+            .query_row(
+                "SELECT id FROM bookmark WHERE url = ?",
+                [&change.url],
+                |row| row.get(0),
+            )
+            .optional()?;
+        tx.execute("DELETE FROM bookmark_tag WHERE bookmark_id = ?", [id])?;
+        tx.execute("DELETE FROM highlight WHERE bookmark_id = ?", [id])?;
+        match &change.bookmark {
+            Some(bookmark) => {
+                let id = match id {
+                    Some(id) => {
+                        tx.execute(
+                            "UPDATE bookmark SET title = ?, description = ?, version = ?,
+                            updated_at = ?, origin = ?, unread = ?, read_at = ?, notes = ?
+                            WHERE id = ?",
+                            (
+                                &bookmark.title,
+                                &bookmark.description,
+                                change.version,
+                                change.changed_at,
+                                &bookmark.origin,
+                                bookmark.unread,
+                                bookmark.read_at,
+                                &bookmark.notes,
+                                id,
+                            ),
+                        )?;
+                        id
+                    }
+                    None => {
+                        tx.execute(
+                            "INSERT INTO bookmark (url, title, description, created_at, version,
+                                updated_at, origin, unread, read_at, notes)
+                            VALUES (?, ?, ?, datetime('now'), ?, ?, ?, ?, ?, ?)",
+                            (
+                                &change.url,
+                                &bookmark.title,
+                                &bookmark.description,
+                                change.version,
+                                change.changed_at,
+                                &bookmark.origin,
+                                bookmark.unread,
+                                bookmark.read_at,
+                                &bookmark.notes,
+                            ),
+                        )?;
+                        tx.last_insert_rowid()
+                    }
+                };
+                add_tags(&tx, id, &bookmark.tags)?;
+                for highlight in &bookmark.highlights {
+                    insert_highlight(&tx, id, &highlight.text, highlight.comment.as_deref())?;
+                }
+            }
+            None => {
+                tx.execute("DELETE FROM bookmark WHERE id = ?", [id])?;
             }
         }
-        Ok(())
+        if record {
+            let kind = match change.bookmark {
+                Some(_) => change.kind,
+                None => ChangeKind::Delete,
+            };
+            tx.execute(
+                "INSERT INTO change_log (url, kind, version, changed_at) VALUES (?, ?, ?, ?)",
+                (
+                    &change.url,
+                    kind.as_str(),
+                    change.version,
+                    change.changed_at,
+                ),
+            )?;
+        }
+        Ok(tx.commit()?)
     }
 }
 
@@ -331,11 +1039,65 @@ impl Database {
 // static-lifetime object will make it stay until the program exits? Would that increase the
 // overall memory of the server every time this function is called without cleaning it up?
 fn repeat_vars(times: usize) -> String {
-    "?,".repeat(times)[..times * 2 - 1].to_string()
+    vec!["?"; times].join(",")
 }
 
-fn add_tags(tx: &Transaction, id: i64, tags: &Vec<String>) -> Result<()> {
-    let mut tag_insert = tx.prepare("INSERT OR IGNORE INTO tag VALUES (?)")?;
+/// Fills in the highlights of `bookmarks`, which are read without them
+fn load_highlights<'a>(
+    connection: &Connection,
+    bookmarks: impl IntoIterator<Item = &'a mut Bookmark>,
+) -> rusqlite::Result<()> {
+    let mut by_id: HashMap<i64, Vec<&mut Bookmark>> = HashMap::new();
+    for bookmark in bookmarks {
+        by_id.entry(bookmark.id).or_default().push(bookmark);
+    }
+    let ids: Vec<i64> = by_id.keys().copied().collect();
+    // Keep well under SQLite's limit on the number of parameters
+    for chunk in ids.chunks(500) {
+        let mut stmt = connection.prepare(&format!(
+            "SELECT bookmark_id, id, text, comment FROM highlight
+            WHERE bookmark_id IN ({}) ORDER BY id",
+            repeat_vars(chunk.len())
+        ))?;
+        let rows = stmt.query_map(params_from_iter(chunk), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                Highlight {
+                    id: row.get(1)?,
+                    text: row.get(2)?,
+                    comment: row.get(3)?,
+                },
+            ))
+        })?;
+        for row in rows {
+            let (bookmark_id, highlight) = row?;
+            for bookmark in by_id.get_mut(&bookmark_id).into_iter().flatten() {
+                bookmark.highlights.push(highlight.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn insert_highlight(
+    tx: &Transaction,
+    id: i64,
+    text: &str,
+    comment: Option<&str>,
+) -> rusqlite::Result<Highlight> {
+    tx.execute(
+        "INSERT INTO highlight (bookmark_id, text, comment) VALUES (?, ?, ?)",
+        (id, text, comment),
+    )?;
+    Ok(Highlight {
+        id: tx.last_insert_rowid(),
+        text: text.to_string(),
+        comment: comment.map(str::to_string),
+    })
+}
+
+fn add_tags(tx: &Transaction, id: i64, tags: &[String]) -> rusqlite::Result<()> {
+    let mut tag_insert = tx.prepare("INSERT OR IGNORE INTO tag (name) VALUES (?)")?;
     for tag in tags {
         tag_insert.execute([&tag])?;
     }
@@ -347,7 +1109,126 @@ fn add_tags(tx: &Transaction, id: i64, tags: &Vec<String>) -> Result<()> {
     Ok(())
 }
 
-fn remove_tags(tx: &Transaction, id: i64, tags: &Vec<String>) -> Result<()> {
+/// (bookmark ID, tag) pairs for `tag` and every tag below it. Mirrored bookmarks are read-only, so
+/// they're left out.
+fn tagged_within(tx: &Transaction, tag: &str) -> rusqlite::Result<Vec<(i64, String)>> {
+    tx.prepare(
+        "SELECT bookmark_id, tag_name FROM bookmark_tag
+        WHERE (tag_name = ? OR substr(tag_name, 1, length(?) + 1) = ? || '/')
+        AND bookmark_id IN (SELECT id FROM bookmark WHERE origin IS NULL)",
+    )?
+    .query_map([tag, tag, tag], |row| Ok((row.get(0)?, row.get(1)?)))?
+    .collect()
+}
+
+/// Removes `tags` from the tag table if no bookmark uses them any more
+fn drop_unused_tags(tx: &Transaction, tags: &[String]) -> rusqlite::Result<()> {
+    let mut stmt = tx.prepare(
+        "DELETE FROM tag WHERE name = ?
+        AND NOT EXISTS (SELECT 1 FROM bookmark_tag WHERE tag_name = ?)",
+    )?;
+    for tag in tags {
+        stmt.execute([tag, tag])?;
+    }
+    Ok(())
+}
+
+/// Logs a tag change for each of `ids`, skipping repeats
+fn log_tag_changes(
+    tx: &Transaction,
+    ids: impl IntoIterator<Item = i64>,
+) -> rusqlite::Result<usize> {
+    let ids: Vec<i64> = ids.into_iter().unique().collect();
+    for &id in &ids {
+        log_change(tx, id, ChangeKind::Tags)?;
+    }
+    Ok(ids.len())
+}
+
+/// Renames `from` and every tag below it on non-mirrored bookmarks and in aliases, returning the
+/// IDs of the bookmarks changed (without logging the changes)
+fn rename_tag(tx: &Transaction, from: &str, to: &str) -> rusqlite::Result<Vec<i64>> {
+    let pairs = tagged_within(tx, from)?;
+    for (id, tag) in &pairs {
+        if let Some(new_tag) = tags::renamed(tag, from, to) {
+            remove_tags(tx, *id, std::slice::from_ref(tag))?;
+            add_tags(tx, *id, &[new_tag])?;
+        }
+    }
+    // Carry descriptions, colors and pins over to the new names, then drop the old ones
+    let old_tags = tx
+        .prepare(
+            "SELECT name FROM tag WHERE name = ? OR substr(name, 1, length(?) + 1) = ? || '/'",
+        )?
+        .query_map([from, from, from], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    let mut renamed_tags = Vec::new();
+    for old_tag in old_tags {
+        match tags::renamed(&old_tag, from, to) {
+            Some(new_tag) if new_tag != old_tag => {
+                tx.execute("INSERT OR IGNORE INTO tag (name) VALUES (?)", [&new_tag])?;
+                tx.execute(
+                    "UPDATE tag SET
+                        description = coalesce(description, (SELECT description FROM tag WHERE name = ?1)),
+                        color = coalesce(color, (SELECT color FROM tag WHERE name = ?1)),
+                        pinned = max(pinned, (SELECT pinned FROM tag WHERE name = ?1))
+                    WHERE name = ?2",
+                    [&old_tag, &new_tag],
+                )?;
+                renamed_tags.push(old_tag);
+            }
+            _ => {}
+        }
+    }
+    drop_unused_tags(tx, &renamed_tags)?;
+    for (alias, tag) in get_tag_aliases(tx)? {
+        if let Some(new_tag) = tags::renamed(&tag, from, to) {
+            tx.execute(
+                "UPDATE tag_alias SET tag = ? WHERE alias = ?",
+                [new_tag, alias],
+            )?;
+        }
+    }
+    Ok(pairs.into_iter().map(|(id, _)| id).collect())
+}
+
+const SELECT_TAG_INFO: &str = "
+    SELECT name, description, color, pinned,
+        (SELECT count(*) FROM bookmark_tag JOIN bookmark ON bookmark.id = bookmark_id
+            WHERE tag_name = name AND deleted_at IS NULL)
+    FROM tag";
+
+fn tag_info_from_row(row: &Row<'_>) -> rusqlite::Result<TagInfo> {
+    Ok(TagInfo {
+        name: row.get(0)?,
+        description: row.get(1)?,
+        color: row.get(2)?,
+        pinned: row.get(3)?,
+        count: row.get(4)?,
+    })
+}
+
+fn get_tag_aliases(connection: &Connection) -> rusqlite::Result<Vec<(String, String)>> {
+    connection
+        .prepare("SELECT alias, tag FROM tag_alias")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+/// Swaps aliases in `tags` for their canonical tags, dropping any duplicates that leaves
+fn resolve_aliases(connection: &Connection, tags: &[String]) -> rusqlite::Result<Vec<String>> {
+    if tags.is_empty() {
+        return Ok(Vec::new());
+    }
+    let aliases = get_tag_aliases(connection)?;
+    Ok(tags
+        .iter()
+        .map(|tag| tags::resolve_alias(tag, &aliases))
+        .unique()
+        .collect())
+}
+
+fn remove_tags(tx: &Transaction, id: i64, tags: &[String]) -> rusqlite::Result<()> {
     let mut bookmark_tag_delete =
         tx.prepare("DELETE FROM bookmark_tag WHERE bookmark_id = ? AND tag_name = ?")?;
     for tag in tags {
@@ -359,33 +1240,37 @@ fn remove_tags(tx: &Transaction, id: i64, tags: &Vec<String>) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use std::error::Error;
+    use std::io::Write;
+
+    use proptest::prelude::*;
+    use rusqlite::types::Value;
+    use tempfile::NamedTempFile;
 
     use super::*;
 
-    fn get_db() -> rusqlite::Result<Database> {
+    fn get_db() -> Result<Database> {
         Database::open(":memory:")
     }
 
-    fn add_bookmark_no_meta(
-        db: &mut Database,
-        url: &str,
-        tags: &Vec<String>,
-    ) -> rusqlite::Result<Bookmark> {
+    fn add_bookmark_no_meta(db: &mut Database, url: &str, tags: &[String]) -> Result<Bookmark> {
         db.add_bookmark(
             url,
             Metadata {
                 title: None,
                 description: None,
+                keywords: Vec::new(),
             },
             tags,
+            false,
         )
+        .map(|(bookmark, _)| bookmark)
     }
 
     // rename_tag should create a new tag if necessary
     #[test]
     fn test_rename_tag_new() -> Result<(), Box<dyn Error>> {
         let mut db = get_db()?;
-        add_bookmark_no_meta(&mut db, &"example.org/one", &vec!["tag-one".to_string()])?;
+        add_bookmark_no_meta(&mut db, "example.org/one", &["tag-one".to_string()])?;
 
         let tags = db.get_tags(true, false)?;
         assert_eq!(tags.len(), 1);
@@ -400,8 +1285,8 @@ mod tests {
     #[test]
     fn test_rename_tag_existing() -> Result<(), Box<dyn Error>> {
         let mut db = get_db()?;
-        add_bookmark_no_meta(&mut db, &"example.org/one", &vec!["tag-one".to_string()])?;
-        add_bookmark_no_meta(&mut db, &"example.org/two", &vec!["tag-two".to_string()])?;
+        add_bookmark_no_meta(&mut db, "example.org/one", &["tag-one".to_string()])?;
+        add_bookmark_no_meta(&mut db, "example.org/two", &["tag-two".to_string()])?;
 
         let tags = db.get_tags(true, false)?;
         assert_eq!(tags.len(), 2);
@@ -423,7 +1308,7 @@ mod tests {
         let tags = db.get_tags(true, false)?;
         assert_eq!(tags.len(), 0);
 
-        add_bookmark_no_meta(&mut db, &"example.org/two", &vec!["tag-two".to_string()])?;
+        add_bookmark_no_meta(&mut db, "example.org/two", &["tag-two".to_string()])?;
         db.rename_tag("tag-one", "tag-two")?;
         let tags = db.get_tags(true, false)?;
         assert_eq!(tags.len(), 1);
@@ -431,5 +1316,128 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_get_single_bookmark_missing() -> Result<(), Box<dyn Error>> {
+        let db = get_db()?;
+        assert!(!db.bookmark_exists(1)?);
+        assert!(matches!(
+            db.get_single_bookmark(1),
+            Err(super::Error::NotFound(1))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_add_existing() -> Result<(), Box<dyn Error>> {
+        let mut db = get_db()?;
+        let no_meta = || Metadata {
+            title: None,
+            description: None,
+            keywords: Vec::new(),
+        };
+        let tags = vec!["one".to_string(), "two".to_string()];
+        let (created, outcome) = db.add_bookmark("example.org", no_meta(), &tags, false)?;
+        assert_eq!(outcome, AddOutcome::Created);
+
+        let more_tags = vec!["two".to_string(), "three".to_string()];
+        let (existing, outcome) = db.add_bookmark("example.org", no_meta(), &more_tags, false)?;
+        assert_eq!(outcome, AddOutcome::AlreadyExists);
+        assert_eq!(existing.id, created.id);
+        assert_eq!(existing.tags.len(), 2);
+
+        let (merged, outcome) = db.add_bookmark("example.org", no_meta(), &more_tags, true)?;
+        assert_eq!(outcome, AddOutcome::Merged);
+        assert_eq!(merged.tags.len(), 3);
+        assert_eq!(db.get_single_bookmark(created.id)?.tags.len(), 3);
+
+        let (_, outcome) = db.add_bookmark("example.org", no_meta(), &more_tags, true)?;
+        assert_eq!(outcome, AddOutcome::AlreadyExists);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_version() -> Result<(), Box<dyn Error>> {
+        let file = NamedTempFile::new()?;
+        let path = file.path().to_str().unwrap();
+        Database::open(path)?.connection.execute(
+            "UPDATE syl_meta SET value = 'one' WHERE key = 'database_version'",
+            (),
+        )?;
+        assert!(matches!(
+            Database::open(path),
+            Err(super::Error::InvalidVersion(Some(v))) if v == "one"
+        ));
+        Connection::open(path)?.execute("DELETE FROM syl_meta", ())?;
+        assert!(matches!(
+            Database::open(path),
+            Err(super::Error::InvalidVersion(None))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_nothing() -> Result<(), Box<dyn Error>> {
+        let db = get_db()?;
+        assert_eq!(db.delete_bookmarks(Vec::new())?, 0);
+        Ok(())
+    }
+
+    // Arbitrary SQLite values, to be written into columns that normally hold something else
+    fn sql_value() -> impl Strategy<Value = Value> {
+        prop_oneof![
+            Just(Value::Null),
+            any::<i64>().prop_map(Value::Integer),
+            any::<f64>().prop_map(Value::Real),
+            ".*".prop_map(Value::Text),
+            proptest::collection::vec(any::<u8>(), 0..32).prop_map(Value::Blob),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_hostile_file(contents in proptest::collection::vec(any::<u8>(), 0..4096)) {
+            let mut file = NamedTempFile::new().unwrap();
+            file.write_all(&contents).unwrap();
+            let _ = Database::open(file.path().to_str().unwrap());
+        }
+
+        #[test]
+        fn test_hostile_version(version in sql_value()) {
+            let file = NamedTempFile::new().unwrap();
+            let path = file.path().to_str().unwrap();
+            Database::open(path).unwrap().connection.execute(
+                "UPDATE syl_meta SET value = ? WHERE key = 'database_version'",
+                [&version],
+            ).unwrap();
+            let _ = Database::open(path);
+        }
+
+        #[test]
+        fn test_hostile_rows(
+            url in sql_value(),
+            title in sql_value(),
+            description in sql_value(),
+            tag in sql_value(),
+        ) {
+            let mut db = get_db().unwrap();
+            add_bookmark_no_meta(&mut db, "example.org", &["tag".to_string()]).unwrap();
+            db.connection.execute(
+                "INSERT INTO bookmark (url, title, description) VALUES (?, ?, ?)",
+                [&url, &title, &description],
+            ).unwrap();
+            let id = db.connection.last_insert_rowid();
+            db.connection.execute("INSERT INTO tag (name) VALUES (?)", [&tag]).ok();
+            db.connection.execute(
+                "INSERT INTO bookmark_tag VALUES (?, ?)",
+                (id, &tag),
+            ).unwrap();
+            let _ = db.search_bookmarks(&None, &Vec::new(), false);
+            let _ = db.search_bookmarks(&Some("a".to_string()), &["tag".to_string()], true);
+            let _ = db.get_tags(true, true);
+            let _ = db.get_single_bookmark(id);
+            let _ = db.rename_tag("tag", "other");
+        }
+    }
+
     // TODO: Tests for edit bookmark
 }
@@ -1,9 +1,21 @@
+pub mod api;
+pub mod client;
 pub mod colors;
 pub mod commands;
 pub mod config;
 pub mod db;
 pub mod migrations;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod query;
+pub mod rules;
+pub mod storage;
+pub mod suggest;
+pub mod sync;
+pub mod tags;
+pub mod users;
 pub mod util;
 pub mod web;
 
-pub mod tests;
+#[cfg(test)]
+mod tests;
@@ -1 +1,128 @@
-pub const MIGRATIONS: &[&str] = &[];
+//! Schema changes since the tables created by `initialize`. `MIGRATIONS[i]` takes a database from
+//! version `i` to `i + 1`; each runs in a transaction along with the version bump.
+
+pub const MIGRATIONS: &[&str] = &[
+    // 1: per-bookmark version counters and the change log used for sync
+    "
+    ALTER TABLE bookmark ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+    ALTER TABLE bookmark ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+    CREATE TABLE change_log (
+        seq             INTEGER PRIMARY KEY AUTOINCREMENT,
+        url             TEXT NOT NULL,
+        kind            TEXT NOT NULL,
+        version         INTEGER NOT NULL,
+        changed_at      INTEGER NOT NULL
+    );
+    INSERT INTO change_log (url, kind, version, changed_at)
+        SELECT url, 'insert', 1, 0 FROM bookmark WHERE url IS NOT NULL ORDER BY id;
+    ",
+    // 2: where mirrored bookmarks came from
+    "ALTER TABLE bookmark ADD COLUMN origin TEXT;",
+    // 3: alternative names for tags
+    "
+    CREATE TABLE tag_alias (
+        alias           TEXT PRIMARY KEY,
+        tag             TEXT NOT NULL
+    );
+    ",
+    // 4: tag descriptions, colors and pins
+    "
+    ALTER TABLE tag ADD COLUMN description TEXT;
+    ALTER TABLE tag ADD COLUMN color TEXT;
+    ALTER TABLE tag ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+    ",
+    // 5: the read-later queue
+    "
+    ALTER TABLE bookmark ADD COLUMN unread INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE bookmark ADD COLUMN read_at INTEGER;
+    ",
+    // 6: the user's own notes, and quoted passages from the page
+    "
+    ALTER TABLE bookmark ADD COLUMN notes TEXT;
+    CREATE TABLE highlight (
+        id              INTEGER PRIMARY KEY AUTOINCREMENT,
+        bookmark_id     INTEGER NOT NULL REFERENCES bookmark (id),
+        text            TEXT NOT NULL,
+        comment         TEXT
+    );
+    ",
+    // 7: when and how often bookmarks were opened with `syl open`
+    "
+    ALTER TABLE bookmark ADD COLUMN last_visited INTEGER;
+    ALTER TABLE bookmark ADD COLUMN visit_count INTEGER NOT NULL DEFAULT 0;
+    ",
+    // 8: users who can sign in to the server
+    "
+    CREATE TABLE user_account (
+        id              INTEGER PRIMARY KEY AUTOINCREMENT,
+        username        TEXT UNIQUE NOT NULL,
+        password_hash   TEXT NOT NULL,
+        created_at      INTEGER NOT NULL
+    );
+    ",
+    // 9: the trash
+    "ALTER TABLE bookmark ADD COLUMN deleted_at INTEGER;",
+];
+
+pub const POSTGRES_MIGRATIONS: &[&str] = &[
+    // 1: per-bookmark version counters and the change log used for sync
+    "
+    ALTER TABLE bookmark ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
+    ALTER TABLE bookmark ADD COLUMN updated_at BIGINT NOT NULL DEFAULT 0;
+    CREATE TABLE change_log (
+        seq             BIGSERIAL PRIMARY KEY,
+        url             TEXT NOT NULL,
+        kind            TEXT NOT NULL,
+        version         BIGINT NOT NULL,
+        changed_at      BIGINT NOT NULL
+    );
+    INSERT INTO change_log (url, kind, version, changed_at)
+        SELECT url, 'insert', 1, 0 FROM bookmark WHERE url IS NOT NULL ORDER BY id;
+    ",
+    // 2: where mirrored bookmarks came from
+    "ALTER TABLE bookmark ADD COLUMN origin TEXT;",
+    // 3: alternative names for tags
+    "
+    CREATE TABLE tag_alias (
+        alias           TEXT PRIMARY KEY,
+        tag             TEXT NOT NULL
+    );
+    ",
+    // 4: tag descriptions, colors and pins
+    "
+    ALTER TABLE tag ADD COLUMN description TEXT;
+    ALTER TABLE tag ADD COLUMN color TEXT;
+    ALTER TABLE tag ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT false;
+    ",
+    // 5: the read-later queue
+    "
+    ALTER TABLE bookmark ADD COLUMN unread BOOLEAN NOT NULL DEFAULT false;
+    ALTER TABLE bookmark ADD COLUMN read_at BIGINT;
+    ",
+    // 6: the user's own notes, and quoted passages from the page
+    "
+    ALTER TABLE bookmark ADD COLUMN notes TEXT;
+    CREATE TABLE highlight (
+        id              BIGSERIAL PRIMARY KEY,
+        bookmark_id     BIGINT NOT NULL REFERENCES bookmark (id),
+        text            TEXT NOT NULL,
+        comment         TEXT
+    );
+    ",
+    // 7: when and how often bookmarks were opened with `syl open`
+    "
+    ALTER TABLE bookmark ADD COLUMN last_visited BIGINT;
+    ALTER TABLE bookmark ADD COLUMN visit_count BIGINT NOT NULL DEFAULT 0;
+    ",
+    // 8: users who can sign in to the server
+    "
+    CREATE TABLE user_account (
+        id              BIGSERIAL PRIMARY KEY,
+        username        TEXT UNIQUE NOT NULL,
+        password_hash   TEXT NOT NULL,
+        created_at      BIGINT NOT NULL
+    );
+    ",
+    // 9: the trash
+    "ALTER TABLE bookmark ADD COLUMN deleted_at BIGINT;",
+];
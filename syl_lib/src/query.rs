@@ -0,0 +1,68 @@
+//! The search query syntax. Words are matched against the URL, title and description as a phrase;
+//! `tag:<tag>` tokens filter by tag (including the tag's descendants) like `--tag` does, and
+//! `is:unread` or `is:read` by whether the bookmark is in the read-later queue.
+
+use crate::tags;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Query {
+    /// The words left over once filters are taken out, if any
+    pub text: Option<String>,
+    pub tags: Vec<String>,
+    /// Some(true) for `is:unread`, Some(false) for `is:read`
+    pub unread: Option<bool>,
+}
+
+pub fn parse(query: &str) -> Query {
+    let mut parsed = Query::default();
+    let mut words = Vec::new();
+    for token in query.split_whitespace() {
+        match token.strip_prefix("tag:") {
+            Some(tag) if !tags::normalize(tag).is_empty() => parsed.tags.push(tags::normalize(tag)),
+            _ => match token {
+                "is:unread" => parsed.unread = Some(true),
+                "is:read" => parsed.unread = Some(false),
+                _ => words.push(token),
+            },
+        }
+    }
+    if !words.is_empty() {
+        parsed.text = Some(words.join(" "));
+    }
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("async tag:lang/rust  runtime"),
+            Query {
+                text: Some("async runtime".to_string()),
+                tags: vec!["lang/rust".to_string()],
+                unread: None,
+            }
+        );
+        assert_eq!(
+            parse("tag:"),
+            Query {
+                text: Some("tag:".to_string()),
+                tags: Vec::new(),
+                unread: None,
+            }
+        );
+        assert_eq!(
+            parse("is:unread rust"),
+            Query {
+                text: Some("rust".to_string()),
+                tags: Vec::new(),
+                unread: Some(true),
+            }
+        );
+        assert_eq!(parse("is:read").unread, Some(false));
+        assert_eq!(parse("  "), Query::default());
+    }
+}
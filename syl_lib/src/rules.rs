@@ -0,0 +1,119 @@
+//! Auto-tagging rules from the config, applied to every bookmark as it's added (and to existing
+//! bookmarks with `syl rules apply`). For example, to tag everything from GitHub with `code`:
+//!
+//! ```toml
+//! [[rules]]
+//! match = "site:github.com"
+//! add_tags = ["code"]
+//! ```
+
+use itertools::Itertools;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+use crate::{db::Bookmark, suggest::host, tags};
+
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Words that must all appear in the URL or title (ignoring case), and `site:<host>` filters
+    /// that match the host or any subdomain of it. A rule with no conditions matches everything.
+    #[serde(rename = "match", default)]
+    pub query: Option<String>,
+    /// A regex the URL must match
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub url: Option<Regex>,
+    /// A regex the title must match; bookmarks without a title never match
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub title: Option<Regex>,
+    pub add_tags: Vec<String>,
+}
+
+impl Rule {
+    pub fn matches(&self, url: &str, title: Option<&str>) -> bool {
+        let query_matches = self.query.as_deref().is_none_or(|query| {
+            let haystack = format!("{url} {}", title.unwrap_or_default()).to_lowercase();
+            query
+                .split_whitespace()
+                .all(|term| match term.strip_prefix("site:") {
+                    Some(site) => host(url).is_some_and(|host| {
+                        let site = site.to_lowercase();
+                        host == site || host.ends_with(&format!(".{site}"))
+                    }),
+                    None => haystack.contains(&term.to_lowercase()),
+                })
+        });
+        query_matches
+            && self.url.as_ref().is_none_or(|url_re| url_re.is_match(url))
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|title_re| title.is_some_and(|title| title_re.is_match(title)))
+    }
+}
+
+/// The tags every rule matching `url` and `title` adds, without duplicates
+pub fn tags_for(rules: &[Rule], url: &str, title: Option<&str>) -> Vec<String> {
+    rules
+        .iter()
+        .filter(|rule| rule.matches(url, title))
+        .flat_map(|rule| rule.add_tags.iter().map(|tag| tags::normalize(tag)))
+        .filter(|tag| !tag.is_empty())
+        .unique()
+        .collect()
+}
+
+/// A bookmark the rules add tags to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RuleMatch {
+    pub bookmark: Bookmark,
+    /// Tags the bookmark didn't have yet
+    pub add_tags: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(toml_rule: &str) -> Rule {
+        toml::from_str(toml_rule).unwrap()
+    }
+
+    #[test]
+    fn test_matches() {
+        let github = rule("match = \"site:github.com\"\nadd_tags = [\"code\"]");
+        assert!(github.matches("https://github.com/rust-lang/rust", None));
+        assert!(github.matches("https://gist.github.com/x", None));
+        assert!(!github.matches("https://notgithub.com/x", None));
+
+        let words = rule("match = \"site:example.com Rust\"\nadd_tags = [\"rust\"]");
+        assert!(words.matches("https://example.com/a", Some("Learning rust")));
+        assert!(!words.matches("https://example.com/a", Some("Learning go")));
+
+        let wiki =
+            rule("url = '^https://wiki\\.internal/'\ntitle = '(?i)howto'\nadd_tags = [\"wiki\"]");
+        assert!(wiki.matches("https://wiki.internal/x", Some("HOWTO: deploy")));
+        assert!(!wiki.matches("https://wiki.internal/x", None));
+        assert!(!wiki.matches("https://example.com/wiki.internal/", Some("howto")));
+
+        assert!(toml::from_str::<Rule>("url = '('\nadd_tags = []").is_err());
+    }
+
+    #[test]
+    fn test_tags_for() {
+        let rules = vec![
+            rule("match = \"site:github.com\"\nadd_tags = [\"code\", \" lang//rust \"]"),
+            rule("match = \"rust\"\nadd_tags = [\"lang/rust\"]"),
+            rule("match = \"python\"\nadd_tags = [\"lang/python\"]"),
+        ];
+        assert_eq!(
+            tags_for(&rules, "https://github.com/rust-lang/rust", None),
+            vec!["code", "lang/rust"]
+        );
+        assert!(tags_for(&rules, "https://example.com", None).is_empty());
+    }
+}
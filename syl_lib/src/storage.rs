@@ -0,0 +1,656 @@
+use std::{error, fmt};
+
+use crate::{
+    config::{self, Config},
+    db::{self, AddOutcome, Bookmark, Database, Highlight, Result, User},
+    sync::Change,
+    tags::TagInfo,
+    web::Metadata,
+};
+
+/// Everything syl needs from a place to keep bookmarks. [Database] (SQLite) is always available;
+/// [crate::postgres::PostgresDatabase] is available with the `postgres` feature.
+pub trait Storage {
+    /// Adds a bookmark for `url`, unless one already exists. If it does and `merge` is true, any
+    /// of `tags` the existing bookmark doesn't have yet are added to it.
+    fn add_bookmark(
+        &mut self,
+        url: &str,
+        metadata: Metadata,
+        tags: &[String],
+        merge: bool,
+    ) -> Result<(Bookmark, AddOutcome)>;
+    /// Finds bookmarks matching `query` and tagged with any (or all) of `tags`, where a tag also
+    /// matches the tags below it
+    fn search_bookmarks(
+        &self,
+        query: &Option<String>,
+        tags: &[String],
+        all_tags: bool,
+    ) -> Result<Vec<Bookmark>>;
+    /// Renames `from` to `to` on every bookmark tagged with it, along with every tag below it
+    /// (`from/x` becomes `to/x`), returning the number of bookmarks changed
+    fn rename_tag(&self, from: &str, to: &str) -> Result<usize>;
+    /// Makes `alias` another name for `tag`, retagging bookmarks tagged with `alias` (or anything
+    /// below it) and returning the number of bookmarks changed. From then on, tags given to
+    /// [Storage::add_bookmark], [Storage::add_tags], [Storage::remove_tags] and
+    /// [Storage::search_bookmarks] are resolved through their aliases.
+    fn add_tag_alias(&self, alias: &str, tag: &str) -> Result<usize>;
+    /// Renames each of `from` to `into` in one go, returning the number of bookmarks changed
+    fn merge_tags(&self, from: &[String], into: &str) -> Result<usize>;
+    /// Removes `tag` and every tag below it from every bookmark, along with aliases for them,
+    /// returning the number of bookmarks changed
+    fn delete_tag(&self, tag: &str) -> Result<usize>;
+    /// Removes tags no bookmark uses any more and that have no details set, returning how many
+    /// were removed
+    fn prune_tags(&self) -> Result<usize>;
+    /// Adds `add` to and removes `remove` from each of `ids` in one transaction, returning the
+    /// number of bookmarks changed
+    fn retag(&mut self, ids: &[i64], add: &[String], remove: &[String]) -> Result<usize>;
+    /// Every (alias, canonical tag) pair
+    fn get_tag_aliases(&self) -> Result<Vec<(String, String)>>;
+    fn get_single_bookmark(&self, id: i64) -> Result<Bookmark>;
+    fn bookmark_exists(&self, id: i64) -> Result<bool>;
+    /// Puts bookmark `id` in the read-later queue, or marks it read (recording when) and takes it
+    /// out of the queue
+    fn set_unread(&mut self, id: i64, unread: bool) -> Result<Bookmark>;
+    /// Moves bookmark `id` to the trash (keeping when it was first trashed) or restores it. The
+    /// trash isn't synced, so this doesn't log a change.
+    fn set_trashed(&mut self, id: i64, trashed: bool) -> Result<Bookmark>;
+    /// Bookmarks in the trash, most recently trashed first
+    fn get_trash(&self) -> Result<Vec<Bookmark>>;
+    /// Counts a visit to bookmark `id` and records when it happened. Visits aren't synced, so this
+    /// doesn't log a change and works on mirrored bookmarks too.
+    fn record_visit(&mut self, id: i64) -> Result<Bookmark>;
+    /// Replaces the notes on bookmark `id`; None removes them
+    fn set_notes(&mut self, id: i64, notes: Option<&str>) -> Result<()>;
+    fn add_highlight(&mut self, id: i64, text: &str, comment: Option<&str>) -> Result<Highlight>;
+    /// Removes a highlight from bookmark `id`, returning whether it had one with that ID
+    fn remove_highlight(&mut self, id: i64, highlight_id: i64) -> Result<bool>;
+    fn add_tags(&mut self, id: i64, tags: &[String]) -> Result<()>;
+    fn remove_tags(&mut self, id: i64, tags: &[String]) -> Result<()>;
+    /// Lists every tag in use along with the number of bookmarks tagged with it, pinned tags first
+    fn get_tags(&self, sort_by_count: bool, reverse: bool) -> Result<Vec<(String, usize)>>;
+    /// A tag's details, resolving aliases, or [db::Error::TagNotFound]
+    fn get_tag_info(&self, name: &str) -> Result<TagInfo>;
+    /// Details of every known tag, whether or not any bookmark uses it
+    fn get_tag_infos(&self) -> Result<Vec<TagInfo>>;
+    /// Sets whichever of a tag's details are given; an empty description or color removes it
+    fn update_tag(
+        &self,
+        name: &str,
+        description: Option<&str>,
+        color: Option<&str>,
+        pinned: Option<bool>,
+    ) -> Result<TagInfo>;
+    /// Every (bookmark ID, tag) pair, for building the tag tree
+    fn get_bookmark_tags(&self) -> Result<Vec<(i64, String)>>;
+    fn delete_bookmarks(&self, ids: Vec<i64>) -> Result<usize>;
+    /// Reads a value from the key/value metadata table (database version, etc.)
+    fn get_meta(&self, key: &str) -> Result<Option<String>>;
+    fn set_meta(&self, key: &str, value: &str) -> Result<()>;
+    /// Adds someone who can sign in to the server as `username`, or fails with
+    /// [db::Error::UserExists]. `password_hash` comes from [crate::users::hash_password].
+    fn add_user(&mut self, username: &str, password_hash: &str) -> Result<User>;
+    /// Every user, by name
+    fn get_users(&self) -> Result<Vec<User>>;
+    /// The stored hash of `username`'s password, if there's such a user
+    fn get_password_hash(&self, username: &str) -> Result<Option<String>>;
+    /// Removes a user, returning whether there was one named `username`
+    fn delete_user(&self, username: &str) -> Result<bool>;
+    /// Entries in the change log after position `since`, oldest first, each carrying its
+    /// bookmark's current state
+    fn changes_since(&self, since: i64, limit: Option<usize>) -> Result<Vec<Change>>;
+    /// The position of the latest entry in the change log, or 0 if it's empty
+    fn last_change_seq(&self) -> Result<i64>;
+    /// The bookmark for `url` as it stands for sync: the bookmark itself with its version if it
+    /// exists, otherwise its latest deletion, if any
+    fn sync_state(&self, url: &str) -> Result<Option<Change>>;
+    /// Makes the bookmark for `change.url` match `change`, creating, overwriting or deleting it.
+    /// The change is only added to the change log if `record` is true.
+    fn write_change(&mut self, change: &Change, record: bool) -> Result<()>;
+}
+
+#[derive(Debug)]
+pub enum OpenError {
+    Config(config::Error),
+    Storage(db::Error),
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpenError::Config(_) => write!(f, "couldn't find the database"),
+            OpenError::Storage(_) => write!(f, "couldn't open the database"),
+        }
+    }
+}
+
+impl error::Error for OpenError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            OpenError::Config(e) => Some(e),
+            OpenError::Storage(e) => Some(e),
+        }
+    }
+}
+
+/// Opens whichever backend `config` points at: PostgreSQL if `database_url` is set, otherwise the
+/// SQLite database at `db_file` (or the default location).
+pub fn open(config: &Config) -> Result<Box<dyn Storage + Send>, OpenError> {
+    match &config.database_url {
+        Some(url) => open_postgres(url).map_err(OpenError::Storage),
+        None => {
+            let path = config.database().map_err(OpenError::Config)?;
+            let db = Database::open(&path).map_err(OpenError::Storage)?;
+            Ok(Box::new(db))
+        }
+    }
+}
+
+#[cfg(feature = "postgres")]
+fn open_postgres(url: &str) -> Result<Box<dyn Storage + Send>> {
+    Ok(Box::new(crate::postgres::PostgresDatabase::open(url)?))
+}
+
+#[cfg(not(feature = "postgres"))]
+fn open_postgres(_url: &str) -> Result<Box<dyn Storage + Send>> {
+    Err(db::Error::Unsupported("PostgreSQL"))
+}
+
+/// Tests every backend should pass. SQLite always runs them; PostgreSQL runs them when built with
+/// the `postgres` feature, against `SYL_TEST_POSTGRES_URL` or else a throwaway server it starts
+/// with `initdb` (see `server_url` in the postgres tests).
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::error::Error;
+
+    use super::*;
+    use crate::sync::ChangeKind;
+    use crate::users;
+
+    type TestResult = std::result::Result<(), Box<dyn Error>>;
+
+    pub(crate) fn no_meta() -> Metadata {
+        Metadata {
+            title: None,
+            description: None,
+            keywords: Vec::new(),
+        }
+    }
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    pub(crate) fn add_and_search(db: &mut dyn Storage) -> TestResult {
+        let (created, outcome) = db.add_bookmark(
+            "https://example.org/one",
+            Metadata {
+                title: Some("Example One".to_string()),
+                description: Some("The first example".to_string()),
+                keywords: Vec::new(),
+            },
+            &tags(&["a", "b"]),
+            false,
+        )?;
+        assert_eq!(outcome, AddOutcome::Created);
+        db.add_bookmark("https://example.org/two", no_meta(), &tags(&["b"]), false)?;
+
+        assert_eq!(db.search_bookmarks(&None, &[], false)?.len(), 2);
+        let found = db.search_bookmarks(&Some("first".to_string()), &[], false)?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, created.id);
+        assert_eq!(
+            db.search_bookmarks(&None, &tags(&["a", "b"]), false)?.len(),
+            2
+        );
+        assert_eq!(
+            db.search_bookmarks(&None, &tags(&["a", "b"]), true)?.len(),
+            1
+        );
+
+        let mut fetched = db.get_single_bookmark(created.id)?;
+        fetched.tags.sort();
+        assert_eq!(fetched.tags, tags(&["a", "b"]));
+        assert!(matches!(
+            db.get_single_bookmark(created.id + 100),
+            Err(db::Error::NotFound(_))
+        ));
+        Ok(())
+    }
+
+    pub(crate) fn duplicates(db: &mut dyn Storage) -> TestResult {
+        db.add_bookmark("example.org", no_meta(), &tags(&["a"]), false)?;
+        let (_, outcome) = db.add_bookmark("example.org", no_meta(), &tags(&["b"]), false)?;
+        assert_eq!(outcome, AddOutcome::AlreadyExists);
+        let (merged, outcome) = db.add_bookmark("example.org", no_meta(), &tags(&["b"]), true)?;
+        assert_eq!(outcome, AddOutcome::Merged);
+        assert_eq!(merged.tags.len(), 2);
+        Ok(())
+    }
+
+    pub(crate) fn edit_tags(db: &mut dyn Storage) -> TestResult {
+        let (bookmark, _) = db.add_bookmark("example.org", no_meta(), &tags(&["a"]), false)?;
+        db.add_tags(bookmark.id, &tags(&["b", "c"]))?;
+        db.remove_tags(bookmark.id, &tags(&["a"]))?;
+        let mut edited = db.get_single_bookmark(bookmark.id)?.tags;
+        edited.sort();
+        assert_eq!(edited, tags(&["b", "c"]));
+
+        assert_eq!(db.rename_tag("b", "d")?, 1);
+        let tag_list = db.get_tags(false, false)?;
+        assert_eq!(tag_list, vec![("c".to_string(), 1), ("d".to_string(), 1)]);
+        Ok(())
+    }
+
+    pub(crate) fn delete(db: &mut dyn Storage) -> TestResult {
+        let (one, _) = db.add_bookmark("example.org/1", no_meta(), &tags(&["a"]), false)?;
+        db.add_bookmark("example.org/2", no_meta(), &tags(&["a"]), false)?;
+        assert_eq!(db.delete_bookmarks(vec![one.id])?, 1);
+        assert_eq!(db.delete_bookmarks(Vec::new())?, 0);
+        assert!(!db.bookmark_exists(one.id)?);
+        assert_eq!(db.get_tags(false, false)?, vec![("a".to_string(), 1)]);
+        Ok(())
+    }
+
+    pub(crate) fn trash(db: &mut dyn Storage) -> TestResult {
+        let (one, _) = db.add_bookmark("example.org/1", no_meta(), &tags(&["a"]), false)?;
+        let (two, _) = db.add_bookmark("example.org/2", no_meta(), &tags(&["a", "b"]), false)?;
+        let since = db.last_change_seq()?;
+
+        let trashed = db.set_trashed(two.id, true)?;
+        let deleted_at = trashed.deleted_at.expect("trashing records when");
+        assert_eq!(db.set_trashed(two.id, true)?.deleted_at, Some(deleted_at));
+        let found = db.search_bookmarks(&None, &[], false)?;
+        assert_eq!(found.iter().map(|b| b.id).collect::<Vec<_>>(), vec![one.id]);
+        assert_eq!(db.get_tags(false, false)?, vec![("a".to_string(), 1)]);
+        assert_eq!(db.get_tag_info("a")?.count, 1);
+        assert_eq!(db.get_bookmark_tags()?, vec![(one.id, "a".to_string())]);
+        assert_eq!(db.prune_tags()?, 0);
+        let trash = db.get_trash()?;
+        assert_eq!((trash.len(), trash[0].id), (1, two.id));
+        assert_eq!(db.last_change_seq()?, since);
+
+        assert_eq!(db.set_trashed(two.id, false)?.deleted_at, None);
+        assert!(db.get_trash()?.is_empty());
+        assert_eq!(db.search_bookmarks(&None, &[], false)?.len(), 2);
+
+        // Adding a trashed bookmark again restores it
+        db.set_trashed(one.id, true)?;
+        let (again, outcome) = db.add_bookmark("example.org/1", no_meta(), &[], false)?;
+        assert_eq!(
+            (again.deleted_at, outcome),
+            (None, AddOutcome::AlreadyExists)
+        );
+        assert!(db.get_trash()?.is_empty());
+        assert!(matches!(
+            db.set_trashed(99, true),
+            Err(db::Error::NotFound(99))
+        ));
+        Ok(())
+    }
+
+    pub(crate) fn meta(db: &mut dyn Storage) -> TestResult {
+        assert_eq!(db.get_meta("missing")?, None);
+        db.set_meta("key", "one")?;
+        db.set_meta("key", "two")?;
+        assert_eq!(db.get_meta("key")?.as_deref(), Some("two"));
+        Ok(())
+    }
+
+    pub(crate) fn users(db: &mut dyn Storage) -> TestResult {
+        let hash = users::hash_password("hunter2")?;
+        let bob = db.add_user("bob", &hash)?;
+        db.add_user("alice", &users::hash_password("swordfish")?)?;
+        assert!(matches!(
+            db.add_user("bob", &hash),
+            Err(db::Error::UserExists(name)) if name == "bob"
+        ));
+        let names: Vec<String> = db.get_users()?.into_iter().map(|u| u.username).collect();
+        assert_eq!(names, tags(&["alice", "bob"]));
+        assert!(bob.created_at > 0);
+
+        let stored = db.get_password_hash("bob")?.unwrap();
+        assert!(users::verify_password("hunter2", &stored));
+        assert!(!users::verify_password("swordfish", &stored));
+        assert_eq!(db.get_password_hash("carol")?, None);
+
+        assert!(db.delete_user("bob")?);
+        assert!(!db.delete_user("bob")?);
+        assert_eq!(db.get_users()?.len(), 1);
+        Ok(())
+    }
+
+    pub(crate) fn change_log(db: &mut dyn Storage) -> TestResult {
+        assert_eq!(db.last_change_seq()?, 0);
+        let (bookmark, _) = db.add_bookmark("example.org/1", no_meta(), &tags(&["a"]), false)?;
+        db.add_bookmark("example.org/2", no_meta(), &[], false)?;
+        db.add_tags(bookmark.id, &tags(&["b"]))?;
+        db.delete_bookmarks(vec![bookmark.id])?;
+
+        let changes = db.changes_since(0, None)?;
+        let kinds: Vec<ChangeKind> = changes.iter().map(|c| c.kind).collect();
+        use ChangeKind::*;
+        assert_eq!(kinds, vec![Insert, Insert, Tags, Delete]);
+        assert_eq!(changes[3].version, 3);
+        assert!(changes[0].bookmark.is_none());
+        assert_eq!(changes[1].bookmark.as_ref().unwrap().url, "example.org/2");
+        assert_eq!(db.last_change_seq()?, changes[3].seq);
+        assert_eq!(db.changes_since(changes[1].seq, Some(1))?[0].kind, Tags);
+
+        let tombstone = db.sync_state("example.org/1")?.unwrap();
+        assert!(tombstone.bookmark.is_none());
+        assert_eq!(db.sync_state("example.org/2")?.unwrap().version, 1);
+        assert!(db.sync_state("example.org/3")?.is_none());
+
+        let mut change = tombstone;
+        change.version = 10;
+        change.bookmark = Some(Bookmark {
+            id: 0,
+            url: "example.org/1".to_string(),
+            title: Some("Restored".to_string()),
+            description: None,
+            tags: tags(&["c"]),
+            origin: Some("https://example.com".to_string()),
+            unread: true,
+            read_at: Some(5),
+            notes: Some("Worth *rereading*".to_string()),
+            highlights: vec![Highlight {
+                id: 0,
+                text: "quoted".to_string(),
+                comment: None,
+            }],
+            created_at: None,
+            last_visited: None,
+            visit_count: 0,
+            deleted_at: None,
+        });
+        db.write_change(&change, false)?;
+        assert_eq!(db.last_change_seq()?, changes[3].seq);
+        let restored = db.sync_state("example.org/1")?.unwrap();
+        assert_eq!(restored.version, 10);
+        let restored = restored.bookmark.unwrap();
+        assert_eq!(restored.tags, tags(&["c"]));
+        assert_eq!(restored.origin.as_deref(), Some("https://example.com"));
+        assert!(restored.unread);
+        assert_eq!(restored.read_at, Some(5));
+        assert_eq!(restored.notes.as_deref(), Some("Worth *rereading*"));
+        assert_eq!(restored.highlights[0].text, "quoted");
+        Ok(())
+    }
+
+    pub(crate) fn notes_and_highlights(db: &mut dyn Storage) -> TestResult {
+        let (bookmark, _) = db.add_bookmark("example.org/1", no_meta(), &[], false)?;
+        db.add_bookmark("example.org/2", no_meta(), &[], false)?;
+        db.set_notes(bookmark.id, Some("My own **summary**"))?;
+        let first = db.add_highlight(bookmark.id, "A quoted passage", None)?;
+        let second = db.add_highlight(bookmark.id, "Another one", Some("Disagree"))?;
+
+        let found = db.get_single_bookmark(bookmark.id)?;
+        assert_eq!(found.notes.as_deref(), Some("My own **summary**"));
+        assert_eq!(found.highlights, vec![first.clone(), second.clone()]);
+        // Notes, highlights and their comments are all searched
+        for query in ["summary", "quoted", "disagree"] {
+            let results = db.search_bookmarks(&Some(query.to_string()), &[], false)?;
+            assert_eq!(results.len(), 1, "searching for {query}");
+            assert_eq!(results[0].highlights.len(), 2);
+        }
+
+        assert!(db.remove_highlight(bookmark.id, first.id)?);
+        assert!(!db.remove_highlight(bookmark.id, first.id)?);
+        db.set_notes(bookmark.id, None)?;
+        let found = db.get_single_bookmark(bookmark.id)?;
+        assert_eq!((found.notes, found.highlights), (None, vec![second]));
+        assert!(matches!(
+            db.add_highlight(99, "x", None),
+            Err(db::Error::NotFound(99))
+        ));
+
+        let changes = db.changes_since(0, None)?;
+        assert_eq!(changes.len(), 7);
+        db.delete_bookmarks(vec![bookmark.id])?;
+        assert!(db
+            .search_bookmarks(&Some("another".to_string()), &[], false)?
+            .is_empty());
+        Ok(())
+    }
+
+    pub(crate) fn read_later(db: &mut dyn Storage) -> TestResult {
+        let (bookmark, _) = db.add_bookmark("example.org/1", no_meta(), &[], false)?;
+        assert!(!bookmark.unread);
+        let since = db.last_change_seq()?;
+
+        let queued = db.set_unread(bookmark.id, true)?;
+        assert!(queued.unread);
+        assert_eq!(queued.read_at, None);
+        let read = db.set_unread(bookmark.id, false)?;
+        assert!(!read.unread);
+        let read_at = read.read_at.expect("marking read records when");
+        // Queuing it again keeps the time it was last read
+        assert_eq!(db.set_unread(bookmark.id, true)?.read_at, Some(read_at));
+
+        let changes = db.changes_since(since, None)?;
+        assert_eq!(changes.len(), 3);
+        assert!(changes.iter().all(|c| c.kind == ChangeKind::Update));
+        assert!(changes[2].bookmark.as_ref().unwrap().unread);
+        assert!(matches!(
+            db.set_unread(99, true),
+            Err(db::Error::NotFound(99))
+        ));
+        Ok(())
+    }
+
+    pub(crate) fn visits(db: &mut dyn Storage) -> TestResult {
+        let (bookmark, _) = db.add_bookmark("example.org/1", no_meta(), &[], false)?;
+        assert!(bookmark.created_at.is_some());
+        assert_eq!(
+            db.get_single_bookmark(bookmark.id)?.created_at,
+            bookmark.created_at
+        );
+        assert_eq!((bookmark.last_visited, bookmark.visit_count), (None, 0));
+        let since = db.last_change_seq()?;
+
+        let first = db.record_visit(bookmark.id)?;
+        assert_eq!(first.visit_count, 1);
+        let last_visited = first.last_visited.expect("visiting records when");
+        let second = db.record_visit(bookmark.id)?;
+        assert_eq!(second.visit_count, 2);
+        assert!(second.last_visited >= Some(last_visited));
+        // Visits are local, so they aren't logged for sync
+        assert!(db.changes_since(since, None)?.is_empty());
+        assert!(matches!(db.record_visit(99), Err(db::Error::NotFound(99))));
+        Ok(())
+    }
+
+    pub(crate) fn tag_hierarchy(db: &mut dyn Storage) -> TestResult {
+        for (url, tag) in [
+            ("example.org/rust", "lang/rust"),
+            ("example.org/async", "lang/rust/async"),
+            ("example.org/rustacean", "lang/rustacean"),
+            ("example.org/go", "lang/go"),
+        ] {
+            db.add_bookmark(url, no_meta(), &tags(&[tag]), false)?;
+        }
+        let urls = |bookmarks: Vec<Bookmark>| {
+            let mut urls: Vec<String> = bookmarks.into_iter().map(|b| b.url).collect();
+            urls.sort();
+            urls
+        };
+        assert_eq!(
+            urls(db.search_bookmarks(&None, &tags(&["lang/rust"]), false)?),
+            vec!["example.org/async", "example.org/rust"]
+        );
+        assert_eq!(
+            urls(db.search_bookmarks(&None, &tags(&["lang", "lang/rust/async"]), true)?),
+            vec!["example.org/async"]
+        );
+        assert_eq!(
+            db.search_bookmarks(&None, &tags(&["lang"]), false)?.len(),
+            4
+        );
+
+        assert_eq!(db.rename_tag("lang/rust", "rust")?, 2);
+        let mut names: Vec<String> = db
+            .get_tags(false, false)?
+            .into_iter()
+            .map(|t| t.0)
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            tags(&["lang/go", "lang/rustacean", "rust", "rust/async"])
+        );
+        assert_eq!(db.get_bookmark_tags()?.len(), 4);
+        Ok(())
+    }
+
+    pub(crate) fn tag_aliases(db: &mut dyn Storage) -> TestResult {
+        db.add_bookmark("example.org/1", no_meta(), &tags(&["js"]), false)?;
+        db.add_bookmark("example.org/2", no_meta(), &tags(&["javascript"]), false)?;
+        assert_eq!(db.add_tag_alias("js", "javascript")?, 1);
+        assert_eq!(
+            db.get_tags(false, false)?,
+            vec![("javascript".to_string(), 2)]
+        );
+
+        let (bookmark, _) = db.add_bookmark(
+            "example.org/3",
+            no_meta(),
+            &tags(&["js/react", "javascript", "js"]),
+            false,
+        )?;
+        assert_eq!(bookmark.tags, tags(&["javascript/react", "javascript"]));
+        assert_eq!(db.search_bookmarks(&None, &tags(&["js"]), false)?.len(), 3);
+        db.remove_tags(bookmark.id, &tags(&["js"]))?;
+        assert_eq!(
+            db.get_single_bookmark(bookmark.id)?.tags,
+            tags(&["javascript/react"])
+        );
+
+        // Aliasing an alias points at the canonical tag, and renames carry aliases along
+        db.add_tag_alias("ecma", "js")?;
+        db.rename_tag("javascript", "lang/javascript")?;
+        let mut aliases = db.get_tag_aliases()?;
+        aliases.sort();
+        assert_eq!(
+            aliases,
+            vec![
+                ("ecma".to_string(), "lang/javascript".to_string()),
+                ("js".to_string(), "lang/javascript".to_string()),
+            ]
+        );
+        assert_eq!(
+            db.search_bookmarks(&None, &tags(&["ecma"]), false)?.len(),
+            3
+        );
+        Ok(())
+    }
+
+    pub(crate) fn tag_maintenance(db: &mut dyn Storage) -> TestResult {
+        let (one, _) = db.add_bookmark("example.org/1", no_meta(), &tags(&["a", "b"]), false)?;
+        let (two, _) = db.add_bookmark("example.org/2", no_meta(), &tags(&["x", "y"]), false)?;
+        let (three, _) = db.add_bookmark("example.org/3", no_meta(), &tags(&["y"]), false)?;
+
+        // Renaming onto a tag the bookmark already has just drops the old one
+        assert_eq!(db.rename_tag("a", "b")?, 1);
+        assert_eq!(db.get_single_bookmark(one.id)?.tags, tags(&["b"]));
+        assert_eq!(db.prune_tags()?, 0);
+
+        assert_eq!(db.merge_tags(&tags(&["x", "y"]), "z")?, 2);
+        assert_eq!(
+            db.get_tags(false, false)?,
+            vec![("b".to_string(), 1), ("z".to_string(), 2)]
+        );
+
+        db.add_tag_alias("zed", "z")?;
+        assert_eq!(db.delete_tag("z")?, 2);
+        assert!(db.get_single_bookmark(two.id)?.tags.is_empty());
+        assert!(db.get_tag_aliases()?.is_empty());
+
+        assert_eq!(
+            db.retag(&[one.id, three.id], &tags(&["new"]), &tags(&["b"]))?,
+            2
+        );
+        assert_eq!(db.get_single_bookmark(one.id)?.tags, tags(&["new"]));
+        assert_eq!(db.get_single_bookmark(three.id)?.tags, tags(&["new"]));
+        // retag leaves "b" unused
+        assert_eq!(db.prune_tags()?, 1);
+        Ok(())
+    }
+
+    pub(crate) fn tag_details(db: &mut dyn Storage) -> TestResult {
+        let (one, _) =
+            db.add_bookmark("example.org/1", no_meta(), &tags(&["alpha", "zulu"]), false)?;
+        db.add_bookmark("example.org/2", no_meta(), &tags(&["alpha"]), false)?;
+        assert!(matches!(
+            db.get_tag_info("missing"),
+            Err(db::Error::TagNotFound(_))
+        ));
+
+        let info = db.update_tag("zulu", Some("The project"), Some("red"), Some(true))?;
+        assert_eq!(info.description.as_deref(), Some("The project"));
+        assert_eq!(
+            (info.color.as_deref(), info.pinned, info.count),
+            (Some("red"), true, 1)
+        );
+        // Pinned tags come first, whatever the sort order
+        let names: Vec<String> = db.get_tags(true, true)?.into_iter().map(|t| t.0).collect();
+        assert_eq!(names, tags(&["zulu", "alpha"]));
+
+        // Renaming carries the details along, and they survive pruning
+        db.rename_tag("zulu", "project/zulu")?;
+        let info = db.get_tag_info("project/zulu")?;
+        assert_eq!((info.color.as_deref(), info.pinned), (Some("red"), true));
+        assert!(db.get_tag_info("zulu").is_err());
+        db.delete_tag("alpha")?;
+        db.remove_tags(one.id, &tags(&["project/zulu"]))?;
+        assert_eq!(db.prune_tags()?, 0);
+        assert_eq!(db.get_tag_infos()?[0].name, "project/zulu");
+
+        let info = db.update_tag("project/zulu", Some(""), None, Some(false))?;
+        assert_eq!((info.description, info.pinned), (None, false));
+        assert_eq!(db.prune_tags()?, 0);
+        db.update_tag("project/zulu", None, Some(""), None)?;
+        assert_eq!(db.prune_tags()?, 1);
+        Ok(())
+    }
+
+    /// Generates one test per shared test function above, each given a fresh database from
+    /// `$open`
+    macro_rules! storage_tests {
+        ($open:expr) => {
+            storage_tests!(
+                $open,
+                add_and_search,
+                duplicates,
+                edit_tags,
+                delete,
+                trash,
+                meta,
+                users,
+                change_log,
+                tag_hierarchy,
+                tag_aliases,
+                tag_maintenance,
+                tag_details,
+                read_later,
+                notes_and_highlights,
+                visits
+            );
+        };
+        ($open:expr, $($name:ident),+) => {
+            $(
+                #[test]
+                fn $name() -> std::result::Result<(), Box<dyn std::error::Error>> {
+                    $crate::storage::tests::$name(&mut $open)
+                }
+            )+
+        };
+    }
+    pub(crate) use storage_tests;
+
+    mod sqlite {
+        super::storage_tests!(crate::db::Database::open(":memory:")?);
+    }
+}
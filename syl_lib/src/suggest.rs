@@ -0,0 +1,254 @@
+//! Tag suggestions for a page about to be bookmarked. Each tag is scored from a few sources: the
+//! page's own keywords, the tags used before for the same site, and the tags of bookmarks whose
+//! titles and descriptions share words with the page's.
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{db::Bookmark, tags, web::Metadata};
+
+/// How many suggestions are given at most
+pub const MAX_SUGGESTIONS: usize = 10;
+
+/// Words that say nothing about what a page is about
+const STOPWORDS: &[&str] = &[
+    "about", "after", "also", "been", "before", "best", "could", "does", "from", "have", "here",
+    "into", "just", "like", "more", "most", "much", "only", "other", "over", "page", "some",
+    "such", "than", "that", "their", "them", "then", "there", "these", "they", "this", "those",
+    "through", "very", "want", "what", "when", "where", "which", "while", "will", "with", "would",
+    "your",
+];
+
+/// Where a suggested tag came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SuggestionSource {
+    /// The page's keywords or OpenGraph tags
+    Keywords,
+    /// Bookmarks from the same site
+    Domain,
+    /// Bookmarks whose titles and descriptions share words with the page's
+    SimilarBookmarks,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagSuggestion {
+    pub tag: String,
+    /// Higher is better; only meaningful compared with the other suggestions
+    pub score: usize,
+    pub sources: Vec<SuggestionSource>,
+}
+
+/// The host of `url` without any leading `www.`, if it has one
+pub fn host(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    let host = url.host_str()?.to_lowercase();
+    Some(host.strip_prefix("www.").unwrap_or(&host).to_string())
+}
+
+/// The distinct lowercase words worth comparing in `text`
+fn terms(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 4)
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+fn bookmark_terms(title: &Option<String>, description: &Option<String>) -> BTreeSet<String> {
+    let mut result = terms(title.as_deref().unwrap_or_default());
+    result.extend(terms(description.as_deref().unwrap_or_default()));
+    result
+}
+
+/// Running totals per tag, with tags resolved through their aliases as they're added
+struct Scores<'a> {
+    aliases: &'a [(String, String)],
+    totals: HashMap<String, (usize, BTreeSet<SuggestionSource>)>,
+}
+
+impl Scores<'_> {
+    fn add(&mut self, tag: &str, score: usize, source: SuggestionSource) {
+        let tag = tags::resolve_alias(&tags::normalize(tag), self.aliases);
+        if tag.is_empty() {
+            return;
+        }
+        let entry = self.totals.entry(tag).or_default();
+        entry.0 += score;
+        entry.1.insert(source);
+    }
+}
+
+/// Suggests tags for `url`, given its `metadata`, every existing `bookmarks` and tag, and the
+/// tag aliases to resolve suggestions through
+pub fn suggest(
+    url: &str,
+    metadata: &Metadata,
+    bookmarks: &[Bookmark],
+    existing_tags: &[(String, usize)],
+    aliases: &[(String, String)],
+) -> Vec<TagSuggestion> {
+    let mut scores = Scores {
+        aliases,
+        totals: HashMap::new(),
+    };
+    // Keywords are matched to an existing tag by name or by its last segment, so `rust` finds
+    // `lang/rust`; keywords without a tag are still suggested, but below those that have one
+    let mut by_name: HashMap<String, &str> = existing_tags
+        .iter()
+        .map(|(tag, _)| {
+            (
+                tag.rsplit('/').next().unwrap_or(tag).to_lowercase(),
+                tag.as_str(),
+            )
+        })
+        .collect();
+    // Full names win over last segments
+    by_name.extend(
+        existing_tags
+            .iter()
+            .map(|(tag, _)| (tag.to_lowercase(), tag.as_str())),
+    );
+    for keyword in &metadata.keywords {
+        let keyword = tags::resolve_alias(&tags::normalize(keyword), aliases);
+        match by_name.get(&keyword.to_lowercase()) {
+            Some(tag) => scores.add(tag, 3, SuggestionSource::Keywords),
+            None => scores.add(&keyword, 1, SuggestionSource::Keywords),
+        }
+    }
+
+    let site = host(url);
+    let page_terms = bookmark_terms(&metadata.title, &metadata.description);
+    for bookmark in bookmarks.iter().filter(|b| b.url != url) {
+        if site.is_some() && host(&bookmark.url) == site {
+            for tag in &bookmark.tags {
+                scores.add(tag, 2, SuggestionSource::Domain);
+            }
+        }
+        let overlap = page_terms
+            .intersection(&bookmark_terms(&bookmark.title, &bookmark.description))
+            .count();
+        if overlap >= 2 {
+            for tag in &bookmark.tags {
+                scores.add(tag, overlap, SuggestionSource::SimilarBookmarks);
+            }
+        }
+    }
+    for term in &page_terms {
+        if let Some(tag) = by_name.get(term) {
+            scores.add(tag, 2, SuggestionSource::SimilarBookmarks);
+        }
+    }
+
+    let mut suggestions: Vec<TagSuggestion> = scores
+        .totals
+        .into_iter()
+        .map(|(tag, (score, sources))| TagSuggestion {
+            tag,
+            score,
+            sources: sources.into_iter().collect(),
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.tag.cmp(&b.tag)));
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bookmark(id: i64, url: &str, title: &str, tags: &[&str]) -> Bookmark {
+        Bookmark {
+            id,
+            url: url.to_string(),
+            title: Some(title.to_string()),
+            description: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            origin: None,
+            unread: false,
+            read_at: None,
+            notes: None,
+            highlights: Vec::new(),
+            created_at: None,
+            last_visited: None,
+            visit_count: 0,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn test_host() {
+        assert_eq!(
+            host("https://www.Example.com/a?b").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(host("not a url"), None);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let bookmarks = vec![
+            bookmark(
+                1,
+                "https://blog.rust-lang.org/a",
+                "Announcing Rust",
+                &["lang/rust"],
+            ),
+            bookmark(
+                2,
+                "https://blog.rust-lang.org/b",
+                "Async in traits",
+                &["news"],
+            ),
+            bookmark(
+                3,
+                "https://example.com/tokio",
+                "Tokio runtime internals explained",
+                &["async"],
+            ),
+            bookmark(4, "https://example.com/other", "Gardening", &["garden"]),
+        ];
+        let existing: Vec<(String, usize)> = ["lang/rust", "news", "async", "garden"]
+            .iter()
+            .map(|t| (t.to_string(), 1))
+            .collect();
+        let aliases = vec![("js".to_string(), "javascript".to_string())];
+        let metadata = Metadata {
+            title: Some("Runtime internals of Tokio".to_string()),
+            description: None,
+            keywords: vec!["Rust".to_string(), "js".to_string()],
+        };
+        let suggestions = suggest(
+            "https://blog.rust-lang.org/c",
+            &metadata,
+            &bookmarks,
+            &existing,
+            &aliases,
+        );
+        let found = |tag: &str| suggestions.iter().find(|s| s.tag == tag).cloned();
+
+        // Keyword matching an existing tag, plus the domain's history
+        let rust = found("lang/rust").unwrap();
+        assert_eq!(rust.score, 5);
+        assert_eq!(
+            rust.sources,
+            vec![SuggestionSource::Keywords, SuggestionSource::Domain]
+        );
+        assert_eq!(suggestions[0].tag, "lang/rust");
+        // Keywords without a tag are resolved through aliases
+        assert_eq!(found("javascript").unwrap().score, 1);
+        assert_eq!(
+            found("news").unwrap().sources,
+            vec![SuggestionSource::Domain]
+        );
+        // "tokio", "runtime" and "internals" overlap
+        assert_eq!(
+            found("async").unwrap().sources,
+            vec![SuggestionSource::SimilarBookmarks]
+        );
+        assert_eq!(found("async").unwrap().score, 3);
+        assert_eq!(found("garden"), None);
+    }
+}
@@ -0,0 +1,666 @@
+//! Two-way sync between a local replica and a server. Every change to a bookmark bumps its version
+//! and is recorded in the database's change log; syncing pushes the local log to the server, pulls
+//! the server's log back, and settles bookmarks changed on both sides with a [ConflictRule]. Both
+//! sides resolve conflicts the same way, so they end up with the same bookmarks.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commands::{Error, Result},
+    db::{self, Bookmark},
+    storage::Storage,
+    tags,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Insert,
+    /// Anything about the bookmark besides its tags changed, e.g. its title, notes or read state
+    Update,
+    /// Tags were added to or removed from the bookmark
+    Tags,
+    /// The bookmark was deleted; changes of this kind are tombstones
+    Delete,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Insert => "insert",
+            ChangeKind::Update => "update",
+            ChangeKind::Tags => "tags",
+            ChangeKind::Delete => "delete",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "insert" => Some(ChangeKind::Insert),
+            "update" => Some(ChangeKind::Update),
+            "tags" => Some(ChangeKind::Tags),
+            "delete" => Some(ChangeKind::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// An entry in a change log. Bookmarks are identified by URL, since IDs differ between databases.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    /// Position in the change log this was read from (0 if it wasn't read from a log)
+    pub seq: i64,
+    pub kind: ChangeKind,
+    pub url: String,
+    pub version: i64,
+    /// Milliseconds since the Unix epoch
+    pub changed_at: i64,
+    /// The bookmark as it is now, or None if it's been deleted since
+    pub bookmark: Option<Bookmark>,
+}
+
+impl Change {
+    /// Whether this change should win over `other` under last-writer-wins
+    fn is_newer_than(&self, other: &Change) -> bool {
+        self.precedence() > other.precedence()
+    }
+
+    /// Orders changes by time, then version, then content, so that both sides of a sync break
+    /// exact ties the same way
+    #[allow(clippy::type_complexity)]
+    fn precedence(&self) -> (i64, i64, Option<(Vec<&str>, Option<&str>, Option<&str>)>) {
+        let content = self.bookmark.as_ref().map(|b| {
+            let mut tags: Vec<&str> = b.tags.iter().map(String::as_str).collect();
+            tags.sort_unstable();
+            (tags, b.title.as_deref(), b.description.as_deref())
+        });
+        (self.changed_at, self.version, content)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictRule {
+    /// The most recent change to a bookmark replaces the other side's entirely
+    LastWriterWins,
+    /// Like last-writer-wins, except tags added on either side are kept. Deletions still follow
+    /// last-writer-wins.
+    #[default]
+    TagUnion,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncRequest {
+    /// The server's change log position as of the client's last sync
+    pub since: i64,
+    /// Changes made on the client since its last sync
+    pub changes: Vec<Change>,
+    pub rule: ConflictRule,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncResponse {
+    /// The server's change log position to send as `since` next time
+    pub cursor: i64,
+    /// Changes made on the server since `since`
+    pub changes: Vec<Change>,
+}
+
+/// A page of a database's change log, for clients that follow it rather than syncing a replica
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangeFeed {
+    /// Changes in log order; a change with no bookmark is a tombstone for a deleted one
+    pub changes: Vec<Change>,
+    /// The position to pass as `since` to get the next page
+    pub cursor: i64,
+    /// Whether there are more changes after this page
+    pub more: bool,
+}
+
+/// The most changes returned in one page of a [ChangeFeed]
+pub const MAX_FEED_PAGE: usize = 1000;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /// Bookmarks sent to the server
+    pub pushed: usize,
+    /// Local bookmarks changed by what the server sent back
+    pub pulled: usize,
+}
+
+pub(crate) fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+/// Keeps only the last change to each URL; each carries the bookmark's current state, so earlier
+/// ones add nothing
+fn collapse(changes: Vec<Change>) -> Vec<Change> {
+    let mut latest: HashMap<String, Change> = HashMap::new();
+    for change in changes {
+        latest.insert(change.url.clone(), change);
+    }
+    let mut changes: Vec<Change> = latest.into_values().collect();
+    changes.sort_by_key(|c| c.seq);
+    changes
+}
+
+/// Decides what the local bookmark should become given a change from the other side, or None if
+/// it should stay as it is
+fn resolve(local: Option<&Change>, remote: &Change, rule: ConflictRule) -> Option<Change> {
+    let local = match local {
+        Some(local) => local,
+        None if remote.bookmark.is_none() => return None,
+        None => return Some(remote.clone()),
+    };
+    let remote_wins = remote.is_newer_than(local);
+    let (mut resolved, other) = if remote_wins {
+        (remote.clone(), local)
+    } else {
+        (local.clone(), remote)
+    };
+    let mut merged = false;
+    if let (ConflictRule::TagUnion, Some(bookmark), Some(other)) =
+        (rule, &mut resolved.bookmark, &other.bookmark)
+    {
+        for tag in &other.tags {
+            if !bookmark.tags.contains(tag) {
+                bookmark.tags.push(tag.clone());
+                merged = true;
+            }
+        }
+    }
+    if merged {
+        // Both sides compute the same merge, so they agree on its version too
+        resolved.kind = ChangeKind::Tags;
+        resolved.version = local.version.max(remote.version) + 1;
+    }
+    if remote_wins || merged {
+        Some(resolved)
+    } else {
+        None
+    }
+}
+
+/// Applies a change from the other side to `storage`, returning whether anything changed
+fn apply(
+    storage: &mut dyn Storage,
+    remote: &Change,
+    rule: ConflictRule,
+    record: bool,
+) -> db::Result<bool> {
+    let local = storage.sync_state(&remote.url)?;
+    match resolve(local.as_ref(), remote, rule) {
+        Some(change) => {
+            storage.write_change(&change, record)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// The server's side of a sync: applies the client's changes, recording them in the server's
+/// change log for other clients, and returns what changed on the server since the client's last
+/// sync. Changes to bookmarks mirrored from another server are ignored, since those are
+/// read-only, and only [mirror] can mark a bookmark as mirrored.
+pub fn handle(storage: &mut dyn Storage, request: SyncRequest) -> db::Result<SyncResponse> {
+    let changes = collapse(storage.changes_since(request.since, None)?);
+    for mut change in collapse(request.changes) {
+        let local = storage.sync_state(&change.url)?;
+        if local
+            .and_then(|local| local.bookmark)
+            .is_some_and(|bookmark| bookmark.origin.is_some())
+        {
+            continue;
+        }
+        if let Some(bookmark) = &mut change.bookmark {
+            bookmark.origin = None;
+        }
+        apply(storage, &change, request.rule, true)?;
+    }
+    Ok(SyncResponse {
+        cursor: storage.last_change_seq()?,
+        changes,
+    })
+}
+
+/// Reads up to `limit` changes after `since` (at most [MAX_FEED_PAGE])
+pub fn feed(storage: &dyn Storage, since: i64, limit: Option<usize>) -> db::Result<ChangeFeed> {
+    let limit = limit.unwrap_or(MAX_FEED_PAGE).min(MAX_FEED_PAGE);
+    let mut changes = storage.changes_since(since, Some(limit + 1))?;
+    let more = changes.len() > limit;
+    changes.truncate(limit);
+    Ok(ChangeFeed {
+        cursor: changes.last().map_or(since, |c| c.seq),
+        changes,
+        more,
+    })
+}
+
+/// Applies changes from another server's change feed to `storage`, marking the bookmarks with
+/// `origin` so they're read-only. With `tag`, only bookmarks with that tag (or one below it) are
+/// copied, and copies that lose it are removed. Bookmarks that exist here without being mirrored
+/// from `origin` are left alone. Returns the number of bookmarks changed.
+fn apply_mirrored(
+    storage: &mut dyn Storage,
+    origin: &str,
+    tag: Option<&str>,
+    changes: Vec<Change>,
+) -> db::Result<usize> {
+    let mut changed = 0;
+    for mut change in collapse(changes) {
+        let mirrored = match storage.sync_state(&change.url)?.and_then(|c| c.bookmark) {
+            Some(local) if local.origin.as_deref() != Some(origin) => continue,
+            Some(_) => true,
+            None => false,
+        };
+        if let Some(bookmark) = &mut change.bookmark {
+            if tag.is_some_and(|tag| !bookmark.tags.iter().any(|t| tags::is_within(t, tag))) {
+                change.bookmark = None;
+            } else {
+                bookmark.origin = Some(origin.to_string());
+            }
+        }
+        if change.bookmark.is_none() && !mirrored {
+            continue;
+        }
+        storage.write_change(&change, true)?;
+        changed += 1;
+    }
+    Ok(changed)
+}
+
+/// Brings the bookmarks mirrored from the server at `origin` up to date, reading its change feed
+/// through `fetch` from where the last call left off; see [apply_mirrored]. Returns the number of
+/// bookmarks changed.
+pub fn mirror(
+    storage: &mut dyn Storage,
+    origin: &str,
+    tag: Option<&str>,
+    mut fetch: impl FnMut(i64) -> Result<ChangeFeed>,
+) -> Result<usize> {
+    let cursor_key = format!("mirror_cursor:{origin}");
+    let mut changed = 0;
+    loop {
+        let since = meta_number(storage, &cursor_key).map_err(Error::Storage)?;
+        let feed = fetch(since)?;
+        changed += apply_mirrored(storage, origin, tag, feed.changes).map_err(Error::Storage)?;
+        storage
+            .set_meta(&cursor_key, &feed.cursor.to_string())
+            .map_err(Error::Storage)?;
+        if !feed.more {
+            return Ok(changed);
+        }
+    }
+}
+
+fn meta_number(storage: &dyn Storage, key: &str) -> db::Result<i64> {
+    Ok(storage
+        .get_meta(key)?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0))
+}
+
+/// The client's side of a sync: sends local changes made since the last sync through `exchange`
+/// and applies the server's changes it gets back. Changes pulled from the server aren't recorded
+/// in the local change log, so they aren't pushed back next time.
+pub fn sync(
+    local: &mut dyn Storage,
+    rule: ConflictRule,
+    exchange: impl FnOnce(&SyncRequest) -> Result<SyncResponse>,
+) -> Result<SyncReport> {
+    let pushed_seq = meta_number(local, "sync_pushed").map_err(Error::Storage)?;
+    let since = meta_number(local, "sync_cursor").map_err(Error::Storage)?;
+    let last_seq = local.last_change_seq().map_err(Error::Storage)?;
+    let request = SyncRequest {
+        since,
+        changes: collapse(
+            local
+                .changes_since(pushed_seq, None)
+                .map_err(Error::Storage)?,
+        ),
+        rule,
+    };
+    let response = exchange(&request)?;
+
+    let mut report = SyncReport {
+        pushed: request.changes.len(),
+        pulled: 0,
+    };
+    for change in collapse(response.changes) {
+        if apply(local, &change, rule, false).map_err(Error::Storage)? {
+            report.pulled += 1;
+        }
+    }
+    local
+        .set_meta("sync_pushed", &last_seq.to_string())
+        .map_err(Error::Storage)?;
+    local
+        .set_meta("sync_cursor", &response.cursor.to_string())
+        .map_err(Error::Storage)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::commands::{DatabaseInterface, Edit, Interface};
+    use crate::db::Database;
+    use crate::storage::tests::no_meta;
+    use crate::web::WebClient;
+
+    type TestResult = std::result::Result<(), Box<dyn std::error::Error>>;
+
+    /// Syncs `client` with `server` as if over HTTP
+    fn sync_with(client: &mut Database, server: &mut Database, rule: ConflictRule) -> SyncReport {
+        sync(client, rule, |request| {
+            let request = serde_json::from_str(&serde_json::to_string(request).unwrap()).unwrap();
+            handle(server, request).map_err(Error::Storage)
+        })
+        .unwrap()
+    }
+
+    fn sorted_tags(db: &Database, url: &str) -> Vec<String> {
+        let mut tags = db.sync_state(url).unwrap().unwrap().bookmark.unwrap().tags;
+        tags.sort();
+        tags
+    }
+
+    fn urls(db: &Database) -> Vec<String> {
+        let mut urls: Vec<String> = db
+            .search_bookmarks(&None, &[], false)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.url)
+            .collect();
+        urls.sort();
+        urls
+    }
+
+    #[test]
+    fn test_offline_adds() -> TestResult {
+        let mut client = Database::open(":memory:")?;
+        let mut server = Database::open(":memory:")?;
+        client.add_bookmark("example.org/client", no_meta(), &["a".to_string()], false)?;
+        server.add_bookmark("example.org/server", no_meta(), &[], false)?;
+
+        let report = sync_with(&mut client, &mut server, ConflictRule::TagUnion);
+        assert_eq!(
+            report,
+            SyncReport {
+                pushed: 1,
+                pulled: 1
+            }
+        );
+        assert_eq!(urls(&client), urls(&server));
+        assert_eq!(urls(&client).len(), 2);
+        assert_eq!(sorted_tags(&server, "example.org/client"), vec!["a"]);
+
+        let report = sync_with(&mut client, &mut server, ConflictRule::TagUnion);
+        assert_eq!(report, SyncReport::default());
+        Ok(())
+    }
+
+    #[test]
+    fn test_notes_and_highlights() -> TestResult {
+        let mut client = Database::open(":memory:")?;
+        let mut server = Database::open(":memory:")?;
+        let (bookmark, _) = client.add_bookmark("example.org", no_meta(), &[], false)?;
+        sync_with(&mut client, &mut server, ConflictRule::TagUnion);
+
+        client.set_notes(bookmark.id, Some("Mine"))?;
+        client.add_highlight(bookmark.id, "Quoted", Some("Agree"))?;
+        sync_with(&mut client, &mut server, ConflictRule::TagUnion);
+
+        let synced = server.search_bookmarks(&None, &[], false)?.remove(0);
+        assert_eq!(synced.notes.as_deref(), Some("Mine"));
+        assert_eq!(synced.highlights.len(), 1);
+        assert_eq!(synced.highlights[0].comment.as_deref(), Some("Agree"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tag_union() -> TestResult {
+        let mut client = Database::open(":memory:")?;
+        let mut server = Database::open(":memory:")?;
+        let (bookmark, _) = client.add_bookmark("example.org", no_meta(), &[], false)?;
+        sync_with(&mut client, &mut server, ConflictRule::TagUnion);
+
+        client.add_tags(bookmark.id, &["client".to_string()])?;
+        let server_id = server.search_bookmarks(&None, &[], false)?[0].id;
+        server.add_tags(server_id, &["server".to_string()])?;
+        sync_with(&mut client, &mut server, ConflictRule::TagUnion);
+
+        assert_eq!(
+            sorted_tags(&client, "example.org"),
+            vec!["client", "server"]
+        );
+        assert_eq!(
+            sorted_tags(&server, "example.org"),
+            vec!["client", "server"]
+        );
+        assert_eq!(
+            sync_with(&mut client, &mut server, ConflictRule::TagUnion),
+            SyncReport::default()
+        );
+        Ok(())
+    }
+
+    /// Replaces the tags on the bookmark for `url`, as a change made at `changed_at`
+    fn retag_at(db: &mut Database, url: &str, tag: &str, changed_at: i64) -> TestResult {
+        let mut change = db.sync_state(url)?.unwrap();
+        change.bookmark.as_mut().unwrap().tags = vec![tag.to_string()];
+        change.kind = ChangeKind::Tags;
+        change.version += 1;
+        change.changed_at = changed_at;
+        db.write_change(&change, true)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_writer_wins() -> TestResult {
+        let mut client = Database::open(":memory:")?;
+        let mut server = Database::open(":memory:")?;
+        client.add_bookmark("example.org/1", no_meta(), &[], false)?;
+        client.add_bookmark("example.org/2", no_meta(), &[], false)?;
+        sync_with(&mut client, &mut server, ConflictRule::LastWriterWins);
+
+        let start = now();
+        retag_at(&mut client, "example.org/1", "client", start + 2_000)?;
+        retag_at(&mut server, "example.org/1", "server", start + 1_000)?;
+        retag_at(&mut client, "example.org/2", "client", start + 1_000)?;
+        retag_at(&mut server, "example.org/2", "server", start + 2_000)?;
+        sync_with(&mut client, &mut server, ConflictRule::LastWriterWins);
+
+        for db in [&client, &server] {
+            assert_eq!(sorted_tags(db, "example.org/1"), vec!["client"]);
+            assert_eq!(sorted_tags(db, "example.org/2"), vec!["server"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_feed() -> TestResult {
+        let mut db = Database::open(":memory:")?;
+        let (bookmark, _) = db.add_bookmark("example.org/1", no_meta(), &[], false)?;
+        db.add_bookmark("example.org/2", no_meta(), &[], false)?;
+        db.delete_bookmarks(vec![bookmark.id])?;
+
+        let page = feed(&db, 0, Some(2))?;
+        assert_eq!(page.changes.len(), 2);
+        assert!(page.more);
+        let page = feed(&db, page.cursor, Some(2))?;
+        assert_eq!(page.changes.len(), 1);
+        assert!(!page.more);
+        assert_eq!(page.changes[0].kind, ChangeKind::Delete);
+        assert!(page.changes[0].bookmark.is_none());
+        let page = feed(&db, page.cursor, None)?;
+        assert!(page.changes.is_empty());
+        assert_eq!(page.cursor, db.last_change_seq()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirror() -> TestResult {
+        const ORIGIN: &str = "https://origin.example.com";
+        let mut origin = Database::open(":memory:")?;
+        let mut local = Database::open(":memory:")?;
+        let (shared, _) = origin.add_bookmark(
+            "example.org/shared",
+            no_meta(),
+            &["team".to_string()],
+            false,
+        )?;
+        origin.add_bookmark("example.org/private", no_meta(), &[], false)?;
+        origin.add_bookmark("example.org/ours", no_meta(), &["team".to_string()], false)?;
+        origin.add_bookmark(
+            "example.org/nested",
+            no_meta(),
+            &["team/docs".to_string()],
+            false,
+        )?;
+        origin.add_bookmark(
+            "example.org/other",
+            no_meta(),
+            &["teams".to_string()],
+            false,
+        )?;
+        local.add_bookmark("example.org/ours", no_meta(), &[], false)?;
+
+        let pull = |origin: &Database, local: &mut Database| {
+            mirror(local, ORIGIN, Some("team"), |since| {
+                feed(origin, since, Some(1)).map_err(Error::Storage)
+            })
+            .unwrap()
+        };
+        assert_eq!(pull(&origin, &mut local), 2);
+        assert_eq!(
+            urls(&local),
+            vec![
+                "example.org/nested",
+                "example.org/ours",
+                "example.org/shared"
+            ]
+        );
+        let copy = local
+            .sync_state("example.org/shared")?
+            .unwrap()
+            .bookmark
+            .unwrap();
+        assert_eq!(copy.origin.as_deref(), Some(ORIGIN));
+        let ours = local
+            .sync_state("example.org/ours")?
+            .unwrap()
+            .bookmark
+            .unwrap();
+        assert_eq!(ours.origin, None);
+        assert_eq!(pull(&origin, &mut local), 0);
+
+        origin.remove_tags(shared.id, &["team".to_string()])?;
+        assert_eq!(pull(&origin, &mut local), 1);
+        assert_eq!(urls(&local), vec!["example.org/nested", "example.org/ours"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mirrored_read_only() -> TestResult {
+        let mut db = Database::open(":memory:")?;
+        let (mut bookmark, _) = db.add_bookmark("example.org", no_meta(), &[], false)?;
+        bookmark.origin = Some("https://origin.example.com".to_string());
+        let mut change = db.sync_state("example.org")?.unwrap();
+        change.bookmark = Some(bookmark.clone());
+        db.write_change(&change, true)?;
+        assert_eq!(db.rename_tag("a", "b")?, 0);
+
+        let mut interface =
+            DatabaseInterface::from(Box::new(db), WebClient::new(Duration::ZERO), Vec::new());
+        let edit = interface.edit(Edit {
+            id: bookmark.id,
+            add_tags: vec!["a".to_string()],
+            remove_tags: Vec::new(),
+            notes: Some("Mine".to_string()),
+            highlight: None,
+            comment: None,
+            remove_highlights: Vec::new(),
+        });
+        assert!(matches!(edit, Err(Error::ReadOnly { id, .. }) if id == bookmark.id));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_leaves_mirrored_alone() -> TestResult {
+        const ORIGIN: &str = "https://origin.example.com";
+        let mut client = Database::open(":memory:")?;
+        let mut server = Database::open(":memory:")?;
+        server.add_bookmark("example.org/mirrored", no_meta(), &[], false)?;
+        let mut change = server.sync_state("example.org/mirrored")?.unwrap();
+        change.bookmark.as_mut().unwrap().origin = Some(ORIGIN.to_string());
+        server.write_change(&change, true)?;
+        server.add_bookmark("example.org/own", no_meta(), &[], false)?;
+        sync_with(&mut client, &mut server, ConflictRule::LastWriterWins);
+
+        // A client can't change or delete a mirrored bookmark through sync, even if it goes
+        // around the checks on its own copy
+        let later = now() + 1_000;
+        retag_at(&mut client, "example.org/mirrored", "client", later)?;
+        let mut deletion = client.sync_state("example.org/mirrored")?.unwrap();
+        deletion.bookmark = None;
+        deletion.version += 1;
+        deletion.changed_at = later + 1_000;
+        let request = SyncRequest {
+            since: server.last_change_seq()?,
+            changes: vec![deletion],
+            rule: ConflictRule::LastWriterWins,
+        };
+        handle(&mut server, request)?;
+        assert_eq!(
+            sync_with(&mut client, &mut server, ConflictRule::LastWriterWins).pushed,
+            1
+        );
+        let mirrored = server
+            .sync_state("example.org/mirrored")?
+            .unwrap()
+            .bookmark
+            .unwrap();
+        assert_eq!(mirrored.origin.as_deref(), Some(ORIGIN));
+        assert!(mirrored.tags.is_empty());
+
+        // Nor can it mark its own bookmarks as mirrored
+        let mut change = client.sync_state("example.org/own")?.unwrap();
+        change.bookmark.as_mut().unwrap().origin = Some(ORIGIN.to_string());
+        change.version += 1;
+        change.changed_at = later;
+        client.write_change(&change, true)?;
+        sync_with(&mut client, &mut server, ConflictRule::LastWriterWins);
+        let own = server.sync_state("example.org/own")?.unwrap();
+        assert_eq!(own.version, change.version);
+        assert_eq!(own.bookmark.unwrap().origin, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deletes() -> TestResult {
+        let mut client = Database::open(":memory:")?;
+        let mut server = Database::open(":memory:")?;
+        let (bookmark, _) = client.add_bookmark("example.org/1", no_meta(), &[], false)?;
+        client.add_bookmark("example.org/2", no_meta(), &[], false)?;
+        sync_with(&mut client, &mut server, ConflictRule::TagUnion);
+
+        client.delete_bookmarks(vec![bookmark.id])?;
+        sync_with(&mut client, &mut server, ConflictRule::TagUnion);
+        assert_eq!(urls(&server), vec!["example.org/2"]);
+
+        // A stale copy of the deleted bookmark coming back from another client loses to the
+        // more recent deletion
+        let mut other = Database::open(":memory:")?;
+        other.add_bookmark("example.org/1", no_meta(), &[], false)?;
+        let mut stale = other.changes_since(0, None)?.remove(0);
+        stale.changed_at = 0;
+        assert!(!apply(&mut server, &stale, ConflictRule::TagUnion, true)?);
+        assert_eq!(urls(&server), vec!["example.org/2"]);
+        Ok(())
+    }
+}
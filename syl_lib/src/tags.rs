@@ -0,0 +1,237 @@
+//! Hierarchical tags. A tag like `lang/rust/async` is a path: it sits under `lang/rust`, which sits
+//! under `lang`. Searching for a tag matches its descendants too, and renaming it renames them.
+//! Tags can also have aliases, which are resolved to the canonical tag wherever tags are given.
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+/// Trims whitespace and drops empty path segments, so `" lang//rust/ "` becomes `lang/rust`
+pub fn normalize(tag: &str) -> String {
+    tag.split('/')
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether `tag` is `ancestor` or somewhere below it
+pub fn is_within(tag: &str, ancestor: &str) -> bool {
+    match tag.strip_prefix(ancestor) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// What `tag` becomes when `from` is renamed to `to`, or None if it isn't `from` or below it
+pub fn renamed(tag: &str, from: &str, to: &str) -> Option<String> {
+    if is_within(tag, from) {
+        Some(format!("{to}{}", &tag[from.len()..]))
+    } else {
+        None
+    }
+}
+
+/// Resolves `tag` to its canonical name using (alias, tag) pairs. An alias also covers the tags
+/// below it, so with `js` aliased to `javascript`, `js/react` becomes `javascript/react`.
+pub fn resolve_alias(tag: &str, aliases: &[(String, String)]) -> String {
+    aliases
+        .iter()
+        .filter(|(alias, _)| is_within(tag, alias))
+        .max_by_key(|(alias, _)| alias.len())
+        .and_then(|(alias, canonical)| renamed(tag, alias, canonical))
+        .unwrap_or_else(|| tag.to_string())
+}
+
+/// Whether two tags look like spellings of the same thing: they differ only in case, or by an edit
+/// or two (scaled to their length; short tags like `go` and `js` are never considered close)
+fn similar(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_lowercase(), b.to_lowercase());
+    let shortest = a.chars().count().min(b.chars().count());
+    a == b || (shortest >= 4 && strsim::damerau_levenshtein(&a, &b) <= 1 + shortest / 8)
+}
+
+/// Groups tags that look like duplicates of each other, each group sorted with the most used tag
+/// (the likely one to keep) first
+pub fn suggest_merges(tags: &[(String, usize)]) -> Vec<Vec<(String, usize)>> {
+    let mut groups: Vec<Vec<(String, usize)>> = Vec::new();
+    for tag in tags {
+        let matching: Vec<usize> = groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| group.iter().any(|(other, _)| similar(&tag.0, other)))
+            .map(|(i, _)| i)
+            .collect();
+        let mut group = vec![tag.clone()];
+        // Merge every group this tag links together, removing from the back to keep indices valid
+        for i in matching.into_iter().rev() {
+            group.extend(groups.remove(i));
+        }
+        groups.push(group);
+    }
+    groups.retain(|group| group.len() > 1);
+    for group in &mut groups {
+        group.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    }
+    groups.sort_by(|a, b| a[0].0.cmp(&b[0].0));
+    groups
+}
+
+/// A tag along with the details that can be set on it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// One of the names in [crate::colors::TAG_COLORS]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Pinned tags are listed before the rest
+    #[serde(default)]
+    pub pinned: bool,
+    /// Number of bookmarks tagged with exactly this tag
+    pub count: usize,
+}
+
+/// A tag in the tree shown by `syl tags --tree`
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagNode {
+    /// The last segment of the path, e.g. `async` for `lang/rust/async`
+    pub name: String,
+    pub path: String,
+    /// Number of bookmarks tagged with this tag or anything below it, each counted once
+    pub count: usize,
+    pub children: Vec<TagNode>,
+}
+
+fn parent(path: &str) -> Option<&str> {
+    path.rsplit_once('/').map(|(parent, _)| parent)
+}
+
+/// Builds the tag tree from every (bookmark ID, tag) pair, sorted by name or by count
+pub fn build_tree(pairs: &[(i64, String)], sort_by_count: bool, reverse: bool) -> Vec<TagNode> {
+    let mut bookmarks: BTreeMap<&str, HashSet<i64>> = BTreeMap::new();
+    for (id, tag) in pairs {
+        let mut path = Some(tag.as_str());
+        while let Some(p) = path {
+            bookmarks.entry(p).or_default().insert(*id);
+            path = parent(p);
+        }
+    }
+    let mut tree = children_of(None, &bookmarks);
+    sort_tree(&mut tree, sort_by_count, reverse);
+    tree
+}
+
+fn children_of(of: Option<&str>, bookmarks: &BTreeMap<&str, HashSet<i64>>) -> Vec<TagNode> {
+    bookmarks
+        .iter()
+        .filter(|(path, _)| parent(path) == of)
+        .map(|(path, ids)| TagNode {
+            name: path.rsplit('/').next().unwrap_or(path).to_string(),
+            path: path.to_string(),
+            count: ids.len(),
+            children: children_of(Some(path), bookmarks),
+        })
+        .collect()
+}
+
+fn sort_tree(nodes: &mut [TagNode], sort_by_count: bool, reverse: bool) {
+    if sort_by_count {
+        nodes.sort_by(|a, b| a.count.cmp(&b.count).then_with(|| a.name.cmp(&b.name)));
+    } else {
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    if reverse {
+        nodes.reverse();
+    }
+    for node in nodes {
+        sort_tree(&mut node.children, sort_by_count, reverse);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(normalize(" lang//rust/ "), "lang/rust");
+        assert!(is_within("lang/rust/async", "lang/rust"));
+        assert!(is_within("lang/rust", "lang/rust"));
+        assert!(!is_within("lang/rustacean", "lang/rust"));
+        assert_eq!(
+            renamed("lang/rust/async", "lang/rust", "rust").as_deref(),
+            Some("rust/async")
+        );
+        assert_eq!(renamed("lang/go", "lang/rust", "rust"), None);
+    }
+
+    #[test]
+    fn test_resolve_alias() {
+        let aliases = vec![
+            ("js".to_string(), "javascript".to_string()),
+            ("js/r".to_string(), "javascript/react".to_string()),
+        ];
+        assert_eq!(resolve_alias("js", &aliases), "javascript");
+        assert_eq!(resolve_alias("js/node", &aliases), "javascript/node");
+        assert_eq!(
+            resolve_alias("js/r/hooks", &aliases),
+            "javascript/react/hooks"
+        );
+        assert_eq!(resolve_alias("json", &aliases), "json");
+    }
+
+    #[test]
+    fn test_suggest_merges() {
+        let tags: Vec<(String, usize)> = [
+            ("JavaScript", 1),
+            ("go", 4),
+            ("javascript", 5),
+            ("javscript", 1),
+            ("js", 2),
+            ("rust", 3),
+            ("rusty", 1),
+        ]
+        .iter()
+        .map(|(tag, count)| (tag.to_string(), *count))
+        .collect();
+        let names = |group: &Vec<(String, usize)>| -> Vec<String> {
+            group.iter().map(|(tag, _)| tag.clone()).collect()
+        };
+        let groups = suggest_merges(&tags);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(
+            names(&groups[0]),
+            vec!["javascript", "JavaScript", "javscript"]
+        );
+        assert_eq!(names(&groups[1]), vec!["rust", "rusty"]);
+    }
+
+    #[test]
+    fn test_tree_counts() {
+        let pairs: Vec<(i64, String)> = [
+            (1, "lang/rust"),
+            (1, "lang/rust/async"),
+            (2, "lang/rust/async"),
+            (3, "lang/go"),
+            (4, "news"),
+        ]
+        .iter()
+        .map(|(id, tag)| (*id, tag.to_string()))
+        .collect();
+        let tree = build_tree(&pairs, false, false);
+        assert_eq!(tree.len(), 2);
+        let lang = &tree[0];
+        assert_eq!((lang.path.as_str(), lang.count), ("lang", 3));
+        assert_eq!(lang.children[0].name, "go");
+        let rust = &lang.children[1];
+        assert_eq!((rust.name.as_str(), rust.count), ("rust", 2));
+        assert_eq!(rust.children[0].path, "lang/rust/async");
+        assert_eq!(rust.children[0].count, 2);
+
+        let by_count = build_tree(&pairs, true, true);
+        assert_eq!(by_count[0].name, "lang");
+        assert_eq!(by_count[0].children[0].name, "rust");
+    }
+}
@@ -1,4 +1,11 @@
-use crate::util::singular_plural;
+use std::time::Duration;
+
+use crate::commands::{DatabaseInterface, Delete, Error, Interface, Search};
+use crate::db::{Database, Error as DatabaseError};
+use crate::storage::{tests::no_meta, Storage};
+use crate::users::secrets_match;
+use crate::util::{error_chain, singular_plural};
+use crate::web::WebClient;
 
 #[test]
 fn test_plural() {
@@ -6,3 +13,84 @@ fn test_plural() {
     assert_eq!(singular_plural("balls", 1), "ball");
     assert_eq!(singular_plural("balls", 2), "balls");
 }
+
+#[test]
+fn test_secrets_match() {
+    assert!(secrets_match("a-long-token", "a-long-token"));
+    assert!(!secrets_match("a-long-tokeN", "a-long-token"));
+    assert!(!secrets_match("a-long", "a-long-token"));
+    assert!(!secrets_match("", "a-long-token"));
+}
+
+#[test]
+fn test_error_chain() {
+    assert_eq!(
+        error_chain(&Error::Storage(DatabaseError::Sqlite(
+            rusqlite::Error::QueryReturnedNoRows
+        ))),
+        "database error: Query returned no rows"
+    );
+    assert_eq!(
+        error_chain(&Error::NotFound(4)),
+        "no bookmark with ID 4 exists"
+    );
+}
+
+#[test]
+fn test_delete() -> Result<(), Box<dyn std::error::Error>> {
+    let mut db = Database::open(":memory:")?;
+    for url in ["example.org/1", "example.org/2", "example.com/3"] {
+        db.add_bookmark(url, no_meta(), &[], false)?;
+    }
+    let interface =
+        DatabaseInterface::from(Box::new(db), WebClient::new(Duration::ZERO), Vec::new());
+    let search = |query: &str| Search {
+        query: Some(query.to_string()),
+        ..Search::default()
+    };
+
+    // An empty search would match everything
+    assert!(matches!(
+        interface.delete(Delete::default()),
+        Err(Error::InvalidQuery(_))
+    ));
+    let dry_run = interface.delete(Delete {
+        search: search("example.org"),
+        dry_run: true,
+        ..Delete::default()
+    })?;
+    assert_eq!(dry_run.len(), 2);
+    assert_eq!(interface.find(Search::default())?.len(), 3);
+
+    let deleted = interface.delete(Delete {
+        ids: vec![1, 3, 1],
+        ..Delete::default()
+    })?;
+    assert_eq!(deleted.iter().map(|b| b.id).collect::<Vec<_>>(), vec![1, 3]);
+    assert!(matches!(
+        interface.delete(Delete {
+            ids: vec![2, 99],
+            ..Delete::default()
+        }),
+        Err(Error::NotFound(99))
+    ));
+    assert!(matches!(
+        interface.delete(Delete {
+            search: search("example"),
+            ids: vec![2],
+            ..Delete::default()
+        }),
+        Err(Error::InvalidQuery(_))
+    ));
+    assert_eq!(
+        interface
+            .delete(Delete {
+                all: true,
+                ..Delete::default()
+            })?
+            .len(),
+        1
+    );
+    assert!(interface.find(Search::default())?.is_empty());
+    Ok(())
+}
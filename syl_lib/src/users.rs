@@ -0,0 +1,34 @@
+//! Passwords for the users kept by [crate::storage::Storage], hashed with Argon2
+
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use password_hash::{rand_core::OsRng, SaltString};
+use subtle::ConstantTimeEq;
+
+pub use password_hash::Error;
+
+/// Hashes `password` with a new random salt, giving a string that also records the algorithm and
+/// its parameters
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// Whether `password` matches `hash`, as made by [hash_password]. A hash that can't be read
+/// matches nothing.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Whether a secret sent by a client (a password or token) is the expected one. The comparison
+/// takes as long however much of it matches, so timing it doesn't help guess the secret.
+pub fn secrets_match(given: &str, expected: &str) -> bool {
+    given.as_bytes().ct_eq(expected.as_bytes()).into()
+}
@@ -1,12 +1,36 @@
+use std::error::Error;
+
+use chrono::{Local, TimeZone};
+
 /// Takes a string in plural form and a count, and returns the &str without the final s. If a word
 /// that doesn't pluralize with a single s is used, this will have to change to return a String.
 pub fn singular_plural(word: &str, count: isize) -> String {
     if count == 1 {
         match word {
             "these" => String::from("this"),
-            _ => word[..word.len() - 1].to_string(),
+            _ => word.strip_suffix('s').unwrap_or(word).to_string(),
         }
     } else {
         word.to_string()
     }
 }
+
+/// Formats an error along with every error in its source chain, e.g. "database error: disk I/O
+/// error".
+pub fn error_chain(error: &dyn Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        message += &format!(": {e}");
+        source = e.source();
+    }
+    message
+}
+
+/// Formats a time in milliseconds since the Unix epoch as a local date and time
+pub fn format_timestamp(millis: i64) -> String {
+    match Local.timestamp_millis_opt(millis).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => millis.to_string(),
+    }
+}
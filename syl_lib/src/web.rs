@@ -9,6 +9,14 @@ use std::time::Duration;
 pub struct Metadata {
     pub title: Option<String>,
     pub description: Option<String>,
+    /// From `<meta name="keywords">` and OpenGraph `article:tag`
+    pub keywords: Vec<String>,
+}
+
+/// What a `<meta>` tag tells us about the page
+enum MetaTag {
+    Description(String),
+    Keywords(Vec<String>),
 }
 
 pub struct WebClient {
@@ -20,10 +28,11 @@ impl WebClient {
         WebClient { timeout }
     }
 
-    pub fn get_metadata(&self, url: &String) -> Result<Metadata, Box<dyn Error>> {
+    pub fn get_metadata(&self, url: &str) -> Result<Metadata, Box<dyn Error>> {
         let mut result = Metadata {
             title: None,
             description: None,
+            keywords: Vec::new(),
         };
 
         let response = ureq::get(url).timeout(self.timeout).call()?;
@@ -40,19 +49,25 @@ impl WebClient {
                     Ok(Event::Start(ref tag)) => {
                         current_tag = from_utf8(tag.name()).unwrap_or("error").to_lowercase();
                         if current_tag == "meta" {
-                            result.description = self.parse_meta_description(tag);
+                            match self.parse_meta(tag) {
+                                Some(MetaTag::Description(description)) => {
+                                    result.description = Some(description)
+                                }
+                                Some(MetaTag::Keywords(keywords)) => {
+                                    result.keywords.extend(keywords)
+                                }
+                                None => {}
+                            }
                         }
                     }
                     // For title tags, we just care about the text, though
-                    Ok(Event::Text(e)) => {
-                        if current_tag == "title" {
-                            result.title = Some(
-                                reader
-                                    .decode(&e.to_owned().unescaped().unwrap_or(e.into_inner()))
-                                    .unwrap_or("")
-                                    .to_string(),
-                            );
-                        }
+                    Ok(Event::Text(e)) if current_tag == "title" => {
+                        result.title = Some(
+                            reader
+                                .decode(&e.to_owned().unescaped().unwrap_or(e.into_inner()))
+                                .unwrap_or("")
+                                .to_string(),
+                        );
                     }
                     Ok(Event::End(_)) => current_tag = String::from(""),
                     Ok(Event::Eof) => break,
@@ -64,7 +79,7 @@ impl WebClient {
         Ok(result)
     }
 
-    fn parse_meta_description(&self, tag: &BytesStart) -> Option<String> {
+    fn parse_meta(&self, tag: &BytesStart) -> Option<MetaTag> {
         // Convert attributes into hashmap, ignoring anything that has errors from the parser or in the
         // utf8 of the key
         let attributes: HashMap<String, String> = tag
@@ -87,14 +102,27 @@ impl WebClient {
             })
             .collect();
 
-        // If it's a name=description tag, we can then get the description tag. This could probably be a
-        // lot more efficient (if we're only looking for the one content attribute in tag, we don't need
-        // to memory for every single attribute) but it works for now.
-        if let Some(name) = attributes.get("name") {
-            if name.as_str() == "description" || name.as_str() == "og:description" {
-                return attributes.get("content").cloned();
+        // OpenGraph uses property= where plain HTML uses name=. This could probably be a lot more
+        // efficient (if we're only looking for the one content attribute in tag, we don't need to
+        // memory for every single attribute) but it works for now.
+        let name = attributes
+            .get("name")
+            .or_else(|| attributes.get("property"))?;
+        let content = attributes.get("content")?;
+        match name.to_lowercase().as_str() {
+            "description" | "og:description" => Some(MetaTag::Description(content.clone())),
+            "keywords" | "news_keywords" => Some(MetaTag::Keywords(
+                content
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+            )),
+            "article:tag" | "og:article:tag" | "book:tag" | "video:tag" => {
+                Some(MetaTag::Keywords(vec![content.trim().to_string()]))
             }
+            _ => None,
         }
-        None
     }
 }